@@ -0,0 +1,231 @@
+//! Drawing many samples at once.
+//!
+//! These read the random numbers they need from the `Rng` in blocks, and
+//! reduce them to a column and a coin flip with Lemire's multiply-and-reject
+//! method, whose rejection threshold only has to be computed once per batch.
+//! The samples have the same distribution as those from `Roulette::sample`,
+//! but aren't the same samples for the same `Rng` state.
+
+use alloc::vec::Vec;
+use rand::Rng;
+
+use index::Probability;
+use {Roulette, RouletteIndex};
+
+/// The number of random words read from the `Rng` at a time, and the number
+/// of indices a `SampleIter` draws at a time.
+const BATCH: usize = 64;
+
+/// A buffer of random words, refilled from an `Rng` when it runs out.
+struct Words {
+    words: [u64; BATCH],
+    position: usize,
+}
+
+impl Words {
+    fn new() -> Words {
+        Words {
+            words: [0; BATCH],
+            position: BATCH,
+        }
+    }
+
+    fn next<R: Rng + ?Sized>(&mut self, rng: &mut R) -> u64 {
+        if self.position == BATCH {
+            rng.fill(&mut self.words[..]);
+            self.position = 0;
+        }
+        self.position += 1;
+        self.words[self.position - 1]
+    }
+
+    /// Returns a uniformly distributed integer in `0..n`, where `threshold` is
+    /// `rejection_threshold(n)`.
+    fn below<R: Rng + ?Sized>(&mut self, n: u64, threshold: u64, rng: &mut R) -> u64 {
+        loop {
+            let product = u128::from(self.next(rng)) * u128::from(n);
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+
+    /// Returns a uniformly distributed number in `[0, 1)`, the same way `rand`
+    /// does.
+    fn unit<R: Rng + ?Sized>(&mut self, rng: &mut R) -> f64 {
+        (self.next(rng) >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+/// Returns `2^64 mod n`: the products whose low word is below this are
+/// rejected, so that every value in `0..n` is equally likely.
+fn rejection_threshold(n: u64) -> u64 {
+    n.wrapping_neg() % n
+}
+
+impl RouletteIndex {
+    /// Fills `dest` with random indices, which is faster than calling
+    /// `RouletteIndex::sample` for each of them.
+    ///
+    /// Panics if the `RouletteIndex` is empty and `dest` isn't.
+    pub fn fill_indices<R: Rng + ?Sized>(&self, dest: &mut [usize], rng: &mut R) {
+        if dest.is_empty() {
+            return;
+        }
+        assert!(!self.is_empty(), "Can't sample from an empty Roulette");
+
+        let mut words = Words::new();
+        if let Some(ref thresholds) = self.single_word {
+            for index in dest {
+                *index = self.single_word_index(thresholds, words.next(rng));
+            }
+            return;
+        }
+        let len = self.len() as u64;
+        let threshold = rejection_threshold(len);
+        match self.probability {
+            Probability::Float(ref probability) => {
+                for index in dest {
+                    let column = words.below(len, threshold, rng) as usize;
+                    *index = if words.unit(rng) < probability[column] {
+                        column
+                    } else {
+                        self.alias[column]
+                    };
+                }
+            }
+            Probability::Exact {
+                ref numerators,
+                denominator,
+                ..
+            } => {
+                let coin_threshold = rejection_threshold(denominator);
+                for index in dest {
+                    let column = words.below(len, threshold, rng) as usize;
+                    let coin = words.below(denominator, coin_threshold, rng);
+                    *index = if coin < numerators[column] {
+                        column
+                    } else {
+                        self.alias[column]
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl<T> Roulette<T> {
+    /// Fills `dest` with the indices of random elements; see
+    /// `RouletteIndex::fill_indices`.
+    ///
+    /// Panics if the `Roulette` is empty and `dest` isn't.
+    pub fn fill_indices<R: Rng + ?Sized>(&self, dest: &mut [usize], rng: &mut R) {
+        self.index.fill_indices(dest, rng)
+    }
+
+    /// Returns `n` random elements, which is faster than calling
+    /// `Roulette::sample` `n` times.
+    ///
+    /// Panics if the `Roulette` is empty and `n` isn't 0.
+    pub fn sample_n<R: Rng + ?Sized>(&self, n: usize, rng: &mut R) -> Vec<&T> {
+        let mut samples = Vec::with_capacity(n);
+        let mut indices = [0; BATCH];
+        let mut remaining = n;
+        while remaining > 0 {
+            let indices = &mut indices[..remaining.min(BATCH)];
+            self.fill_indices(indices, rng);
+            samples.extend(indices.iter().map(|&index| &self.items[index]));
+            remaining -= indices.len();
+        }
+        samples
+    }
+
+    /// Returns an infinite iterator over random elements, which draws them in
+    /// batches. An empty `Roulette` gives an empty iterator.
+    ///
+    /// The iterator takes ownership of `rng`, which can be a `&mut` reference
+    /// to an `Rng` that's needed again afterwards.
+    pub fn sample_iter<R: Rng>(&self, rng: R) -> SampleIter<'_, T, R> {
+        SampleIter {
+            roulette: self,
+            rng,
+            indices: [0; BATCH],
+            position: BATCH,
+        }
+    }
+}
+
+/// An infinite iterator over random elements of a `Roulette`, returned by
+/// `Roulette::sample_iter`.
+pub struct SampleIter<'a, T: 'a, R> {
+    roulette: &'a Roulette<T>,
+    rng: R,
+    indices: [usize; BATCH],
+    position: usize,
+}
+
+impl<'a, T, R: Rng> Iterator for SampleIter<'a, T, R> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.roulette.is_empty() {
+            return None;
+        }
+        if self.position == BATCH {
+            self.roulette.fill_indices(&mut self.indices, &mut self.rng);
+            self.position = 0;
+        }
+        self.position += 1;
+        Some(&self.roulette.items[self.indices[self.position - 1]])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.roulette.is_empty() {
+            (0, Some(0))
+        } else {
+            (usize::MAX, None)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use stats;
+    use stats::tests::{assert_counts_fit, WEIGHTS};
+
+    #[test]
+    fn distribution() {
+        let index = RouletteIndex::new(&WEIGHTS);
+        let mut indices = vec![0; 200_000];
+        index.fill_indices(&mut indices, &mut StdRng::seed_from_u64(15));
+        assert_counts_fit(&stats::count_indices(
+            WEIGHTS.len(),
+            indices.iter().cloned(),
+        ));
+
+        let weights = [3, 0, 1, 5, 10, 2];
+        let index = RouletteIndex::from_integer_weights(&weights);
+        index.fill_indices(&mut indices, &mut StdRng::seed_from_u64(15));
+        let counts = stats::count_indices(weights.len(), indices.iter().cloned());
+        let weights: Vec<f64> = weights.iter().map(|&weight| weight as f64).collect();
+        assert!(stats::chi_squared(&counts, &weights).p_value > 0.001);
+    }
+
+    #[test]
+    fn samples() {
+        let mut rng = rand::thread_rng();
+        let roulette = Roulette::new(vec![('a', 0.0), ('b', 1.0), ('c', 0.0)]);
+        assert_eq!(roulette.sample_n(150, &mut rng), vec![&'b'; 150]);
+        let samples: Vec<&char> = roulette.sample_iter(&mut rng).take(150).collect();
+        assert_eq!(samples, vec![&'b'; 150]);
+
+        let empty: Roulette<char> = Roulette::empty();
+        assert!(empty.sample_n(0, &mut rng).is_empty());
+        assert_eq!(empty.sample_iter(&mut rng).next(), None);
+        empty.fill_indices(&mut [], &mut rng);
+    }
+}
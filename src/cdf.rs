@@ -0,0 +1,173 @@
+use alloc::vec::Vec;
+use rand::Rng;
+
+use error::{self, RouletteError};
+
+/// A variant of `Roulette` that stores the cumulative distribution of the
+/// elements and samples by binary search, in O(log n) time.
+///
+/// Besides sampling, it answers cumulative distribution and quantile queries,
+/// and can turn uniforms generated elsewhere into samples by inverse
+/// transform sampling.
+pub struct CdfRoulette<T> {
+    items: Vec<T>,
+    /// The probability of each element or one before it; the last is 1.
+    cumulative: Vec<f64>,
+}
+
+impl<T> CdfRoulette<T> {
+    /// Creates a `CdfRoulette` with the given probabilities for a set of
+    /// elements; see `Roulette::new`.
+    ///
+    /// Panics if the probabilities are invalid; see `CdfRoulette::try_new`.
+    pub fn new(items: Vec<(T, f64)>) -> CdfRoulette<T> {
+        match CdfRoulette::try_new(items) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `CdfRoulette` like `CdfRoulette::new`, but returns an error
+    /// instead of panicking if the probabilities are all zero, or if any are
+    /// negative, NaN or infinite.
+    ///
+    /// An empty `Vec` gives an empty `CdfRoulette`.
+    pub fn try_new(items: Vec<(T, f64)>) -> Result<CdfRoulette<T>, RouletteError> {
+        if items.is_empty() {
+            return Ok(CdfRoulette {
+                items: Vec::new(),
+                cumulative: Vec::new(),
+            });
+        }
+        let total = error::check_weights(items.iter().map(|item| item.1))?;
+        let mut sum = 0.0;
+        let mut cumulative = Vec::with_capacity(items.len());
+        let items = items
+            .into_iter()
+            .map(|(item, weight)| {
+                // The sum is taken in the same order as `check_weights`, so
+                // the last one is exactly 1.
+                sum += weight;
+                cumulative.push(sum / total);
+                item
+            })
+            .collect();
+        Ok(CdfRoulette { items, cumulative })
+    }
+
+    /// Returns the number of elements, including those with zero probability.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the `CdfRoulette` has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the elements, in the order they were given.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns the probability of sampling the element at `index` or one
+    /// before it.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn cdf(&self, index: usize) -> f64 {
+        self.cumulative[index]
+    }
+
+    /// Returns the first element whose `cdf` is at least `u`, or for a `u` of
+    /// 0, the first element with a non-zero probability.
+    ///
+    /// Panics if `u` isn't between 0 and 1, or the `CdfRoulette` is empty.
+    pub fn quantile(&self, u: f64) -> &T {
+        assert!((0.0..=1.0).contains(&u), "Quantile must be between 0 and 1");
+        assert!(!self.is_empty(), "Can't sample from an empty Roulette");
+        &self.items[self
+            .cumulative
+            .partition_point(|&cdf| cdf < u || cdf == 0.0)]
+    }
+
+    /// Returns a random element; each element's chance of being returned is
+    /// proportional to its probability.
+    ///
+    /// Panics if the `CdfRoulette` is empty.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &T {
+        self.try_sample(rng)
+            .expect("Can't sample from an empty Roulette")
+    }
+
+    /// Returns a random element like `CdfRoulette::sample`, or `None` if the
+    /// `CdfRoulette` is empty.
+    pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.sample_from_uniform(rng.gen()))
+        }
+    }
+
+    /// Returns the element that `sample` would return for the uniform `u`: the
+    /// first one whose `cdf` is greater than `u`. Uniformly distributed values
+    /// of `u` give samples with the right probabilities, and elements with
+    /// zero probability are never returned.
+    ///
+    /// Panics if `u` isn't in `0.0..1.0`, or the `CdfRoulette` is empty.
+    pub fn sample_from_uniform(&self, u: f64) -> &T {
+        assert!((0.0..1.0).contains(&u), "Uniform must be in 0.0..1.0");
+        assert!(!self.is_empty(), "Can't sample from an empty Roulette");
+        &self.items[self.cumulative.partition_point(|&cdf| cdf <= u)]
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use stats::tests::{assert_fits, WEIGHTS};
+    use RouletteIndex;
+
+    #[test]
+    fn queries() {
+        let roulette = CdfRoulette::new(vec![
+            ('a', 0.0),
+            ('b', 1.0),
+            ('c', 0.0),
+            ('d', 3.0),
+            ('e', 0.0),
+        ]);
+        let cdf: Vec<f64> = (0..5).map(|i| roulette.cdf(i)).collect();
+        assert_eq!(cdf, vec![0.0, 0.25, 0.25, 1.0, 1.0]);
+
+        assert_eq!(roulette.quantile(0.0), &'b');
+        assert_eq!(roulette.quantile(0.25), &'b');
+        assert_eq!(roulette.quantile(0.3), &'d');
+        assert_eq!(roulette.quantile(1.0), &'d');
+
+        assert_eq!(roulette.sample_from_uniform(0.0), &'b');
+        assert_eq!(roulette.sample_from_uniform(0.25), &'d');
+        assert_eq!(roulette.sample_from_uniform(0.999_999), &'d');
+    }
+
+    #[test]
+    fn sampling() {
+        let roulette = CdfRoulette::new(
+            WEIGHTS
+                .iter()
+                .enumerate()
+                .map(|(i, &weight)| (i, weight))
+                .collect(),
+        );
+        assert_fits(22, |rng| *roulette.sample(rng));
+
+        let alias = RouletteIndex::new(&WEIGHTS).effective_probabilities();
+        for (i, &probability) in alias.iter().enumerate() {
+            let previous = if i == 0 { 0.0 } else { roulette.cdf(i - 1) };
+            assert!((roulette.cdf(i) - previous - probability).abs() < 1e-12);
+        }
+
+        let empty: CdfRoulette<char> = CdfRoulette::new(Vec::new());
+        assert_eq!(empty.try_sample(&mut rand::thread_rng()), None);
+    }
+}
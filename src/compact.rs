@@ -0,0 +1,339 @@
+use alloc::vec::Vec;
+use core::mem;
+use rand::Rng;
+
+use error::{self, RouletteError};
+use index::{self, Probability};
+use {Roulette, RouletteIndex};
+
+/// A `Roulette` that uses 8 bytes per element besides the element itself,
+/// rather than 16, for very large tables.
+///
+/// Each column's alias and coin threshold are stored next to each other as
+/// `u32`s, so a sample reads a single cache line. Samples are drawn like with
+/// `SamplingMethod::SingleWord`, with the same small bias.
+///
+/// A `CompactRoulette` can hold at most 2^32 elements.
+pub struct CompactRoulette<T> {
+    items: Vec<T>,
+    entries: Vec<Entry>,
+}
+
+/// A column of the alias table. The threshold is a fraction of 2^32.
+#[derive(Clone, Copy)]
+struct Entry {
+    alias: u32,
+    threshold: u32,
+}
+
+impl<T> CompactRoulette<T> {
+    /// Creates a `CompactRoulette` with the given probabilities for a set of
+    /// elements; see `Roulette::new`.
+    ///
+    /// Panics if the probabilities are invalid or there are too many
+    /// elements; see `CompactRoulette::try_new`.
+    pub fn new(items: Vec<(T, f64)>) -> CompactRoulette<T> {
+        match CompactRoulette::try_new(items) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `CompactRoulette` like `CompactRoulette::new`, but returns an
+    /// error instead of panicking if the probabilities are invalid (see
+    /// `Roulette::try_new`) or if there are more than 2^32 elements.
+    ///
+    /// The table is built directly in its compact form, so besides the
+    /// elements this needs at most 16 bytes per element while it runs: 8 for
+    /// the probabilities, which are dropped at the end, and 8 for the table.
+    pub fn try_new(items: Vec<(T, f64)>) -> Result<CompactRoulette<T>, RouletteError> {
+        check_len(items.len())?;
+        let (items, mut weights): (Vec<T>, Vec<f64>) = items.into_iter().unzip();
+        if items.is_empty() {
+            return Ok(CompactRoulette {
+                items,
+                entries: Vec::new(),
+            });
+        }
+        let sum = error::check_weights(weights.iter().cloned())?;
+        let entries = build(&mut weights, sum);
+        Ok(CompactRoulette { items, entries })
+    }
+
+    /// Converts a `Roulette` to a `CompactRoulette`, or returns an error if it
+    /// has more than 2^32 elements.
+    ///
+    /// The `Roulette`'s table, which takes 16 bytes per element, is only
+    /// dropped once the compact one is built, so this needs more memory than
+    /// `CompactRoulette::try_new`.
+    pub fn try_from_roulette(roulette: Roulette<T>) -> Result<CompactRoulette<T>, RouletteError> {
+        let Roulette { items, index } = roulette;
+        check_len(items.len())?;
+        let entries = (0..index.len())
+            .map(|column| match index.threshold(column) {
+                Some(threshold) => Entry {
+                    alias: index.alias[column] as u32,
+                    threshold,
+                },
+                None => Entry {
+                    alias: column as u32,
+                    threshold: u32::MAX,
+                },
+            })
+            .collect();
+        Ok(CompactRoulette { items, entries })
+    }
+
+    /// Returns the number of elements, including those with zero probability.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the `CompactRoulette` has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the elements, in the order they were given.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns a random element; each element's chance of being returned is
+    /// proportional to its probability, up to the bias of
+    /// `SamplingMethod::SingleWord`.
+    ///
+    /// Panics if the `CompactRoulette` is empty.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &T {
+        &self.items[self.sample_index(rng)]
+    }
+
+    /// Returns a random element like `CompactRoulette::sample`, or `None` if
+    /// the `CompactRoulette` is empty.
+    pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        self.try_sample_index(rng).map(|index| &self.items[index])
+    }
+
+    /// Returns the index of a random element, chosen like in
+    /// `CompactRoulette::sample`.
+    ///
+    /// Panics if the `CompactRoulette` is empty.
+    pub fn sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.try_sample_index(rng)
+            .expect("Can't sample from an empty Roulette")
+    }
+
+    /// Returns the index of a random element like
+    /// `CompactRoulette::sample_index`, or `None` if the `CompactRoulette` is
+    /// empty.
+    pub fn try_sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let product = u128::from(rng.gen::<u64>()) * self.len() as u128;
+        let column = (product >> 64) as usize;
+        let coin = (product as u64 >> 32) as u32;
+        let entry = self.entries[column];
+        Some(if coin < entry.threshold {
+            column
+        } else {
+            entry.alias as usize
+        })
+    }
+
+    /// Returns the probability of each element being returned by `sample`,
+    /// as represented by the fixed-point thresholds. This leaves out the
+    /// much smaller bias from mapping a `u64` onto the columns.
+    pub fn effective_probabilities(&self) -> Vec<f64> {
+        const SCALE: f64 = (1u64 << 32) as f64;
+        let column = 1.0 / self.len() as f64;
+        let mut probabilities = vec![0.0; self.len()];
+        for (i, entry) in self.entries.iter().enumerate() {
+            let heads = f64::from(entry.threshold) / SCALE;
+            probabilities[i] += heads * column;
+            probabilities[entry.alias as usize] += (1.0 - heads) * column;
+        }
+        probabilities
+    }
+
+    /// Returns the number of bytes used by the `CompactRoulette`, including
+    /// its spare capacity but not any memory owned by the elements.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<CompactRoulette<T>>()
+            + self.items.capacity() * mem::size_of::<T>()
+            + self.entries.capacity() * mem::size_of::<Entry>()
+    }
+}
+
+fn check_len(len: usize) -> Result<(), RouletteError> {
+    if len as u64 > u64::from(u32::MAX) + 1 {
+        return Err(RouletteError::TooManyItems { len });
+    }
+    Ok(())
+}
+
+/// Builds the alias table like `RouletteIndex::new`, but directly as
+/// `Entry`s. Until a column is done, its alias links it into the list of
+/// small or large columns, so the only other memory needed is `weights`,
+/// which is overwritten with the scaled probabilities.
+fn build(weights: &mut [f64], sum: f64) -> Vec<Entry> {
+    let len = weights.len();
+    let mut entries = vec![
+        Entry {
+            alias: 0,
+            threshold: 0,
+        };
+        len
+    ];
+    let mut small = List::default();
+    let mut large = List::default();
+    for (i, weight) in weights.iter_mut().enumerate() {
+        *weight = *weight / sum * len as f64;
+        if *weight >= 1.0 {
+            large.push(&mut entries, i);
+        } else {
+            small.push(&mut entries, i);
+        }
+    }
+
+    while small.len > 0 && large.len > 0 {
+        let less = small.pop(&entries);
+        let more = large.pop(&entries);
+        entries[less] = Entry {
+            alias: more as u32,
+            threshold: index::fixed_point(weights[less]),
+        };
+        weights[more] = (weights[more] + weights[less]) - 1.0;
+        if weights[more] >= 1.0 {
+            large.push(&mut entries, more);
+        } else {
+            small.push(&mut entries, more);
+        }
+    }
+
+    // Due to rounding, either list can have columns left, which keep their
+    // whole column.
+    for list in &mut [small, large] {
+        while list.len > 0 {
+            let column = list.pop(&entries);
+            entries[column] = Entry {
+                alias: column as u32,
+                threshold: u32::MAX,
+            };
+        }
+    }
+    entries
+}
+
+/// A stack of columns, linked through the aliases of their entries.
+#[derive(Default)]
+struct List {
+    head: usize,
+    len: usize,
+}
+
+impl List {
+    fn push(&mut self, entries: &mut [Entry], column: usize) {
+        entries[column].alias = self.head as u32;
+        self.head = column;
+        self.len += 1;
+    }
+
+    fn pop(&mut self, entries: &[Entry]) -> usize {
+        let column = self.head;
+        self.head = entries[column].alias as usize;
+        self.len -= 1;
+        column
+    }
+}
+
+impl<T> From<Roulette<T>> for CompactRoulette<T> {
+    /// Converts a `Roulette` to a `CompactRoulette`.
+    ///
+    /// Panics if it has more than 2^32 elements.
+    fn from(roulette: Roulette<T>) -> CompactRoulette<T> {
+        match CompactRoulette::try_from_roulette(roulette) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid Roulette: {}", err),
+        }
+    }
+}
+
+impl RouletteIndex {
+    /// Returns the number of bytes used by the `RouletteIndex`, including its
+    /// spare capacity.
+    pub fn memory_usage(&self) -> usize {
+        let probability = match self.probability {
+            Probability::Float(ref probability) => probability.capacity() * mem::size_of::<f64>(),
+            Probability::Exact { ref numerators, .. } => {
+                numerators.capacity() * mem::size_of::<u64>()
+            }
+        };
+        let single_word = self.single_word.as_ref().map_or(0, |thresholds| {
+            thresholds.capacity() * mem::size_of::<u32>()
+        });
+        mem::size_of::<RouletteIndex>()
+            + self.alias.capacity() * mem::size_of::<usize>()
+            + probability
+            + single_word
+            + self.underflow.capacity() * mem::size_of::<(usize, f64)>()
+    }
+}
+
+impl<T> Roulette<T> {
+    /// Returns the number of bytes used by the `Roulette`, including its spare
+    /// capacity but not any memory owned by the elements.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Roulette<T>>() - mem::size_of::<RouletteIndex>()
+            + self.items.capacity() * mem::size_of::<T>()
+            + self.index.memory_usage()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use stats::tests::{assert_fits, WEIGHTS};
+
+    #[test]
+    fn sampling() {
+        let roulette = CompactRoulette::new(WEIGHTS.iter().map(|&weight| ((), weight)).collect());
+        assert_fits(17, |rng| roulette.sample_index(rng));
+
+        let sum: f64 = WEIGHTS.iter().sum();
+        for (probability, weight) in roulette.effective_probabilities().iter().zip(&WEIGHTS) {
+            assert!((probability - weight / sum).abs() < 1e-9);
+        }
+
+        let empty: CompactRoulette<char> = CompactRoulette::new(Vec::new());
+        assert_eq!(empty.try_sample(&mut rand::thread_rng()), None);
+    }
+
+    #[test]
+    fn from_roulette() {
+        let items: Vec<((), f64)> = WEIGHTS.iter().map(|&weight| ((), weight)).collect();
+        let direct = CompactRoulette::new(items.clone()).effective_probabilities();
+        let converted = CompactRoulette::from(Roulette::new(items)).effective_probabilities();
+        for (direct, converted) in direct.iter().zip(&converted) {
+            assert!((direct - converted).abs() < 1e-9);
+        }
+
+        let roulette = Roulette::from_integer_weights(vec![('a', 1), ('b', 0), ('c', 3)]);
+        let compact = CompactRoulette::from(roulette);
+        assert_eq!(compact.effective_probabilities(), vec![0.25, 0.0, 0.75]);
+        assert_eq!(
+            CompactRoulette::try_new(vec![('a', 0.0)]).err(),
+            Some(RouletteError::ZeroSum)
+        );
+    }
+
+    #[test]
+    fn memory_usage() {
+        let items: Vec<(u8, f64)> = (0..1000).map(|i| (i as u8, 1.0 + i as f64)).collect();
+        let roulette = Roulette::new(items.clone());
+        let compact = CompactRoulette::from(Roulette::new(items));
+        assert!(compact.memory_usage() >= 9 * 1000);
+        assert!(compact.memory_usage() < 9 * 1000 + 100);
+        assert!(roulette.memory_usage() >= 17 * 1000);
+    }
+}
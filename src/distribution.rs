@@ -0,0 +1,362 @@
+//! Implementations of `Distribution` for `Roulette`, for the version of `rand`
+//! that `Roulette::sample` uses as well as for newer versions behind the
+//! `rand_0_8`, `rand_0_9` and `rand_0_10` features.
+//!
+//! Each version gets three implementations: one for `Roulette<T>` that returns
+//! clones of the elements, one for `Elements` that returns references to them,
+//! and one for `RouletteIndex`. All of them panic if the `Roulette` is empty.
+//!
+//! `Elements` can't simply be `&Roulette<T>`, since that would overlap with
+//! `rand`'s implementation of `Distribution` for references.
+
+use rand::distributions::Distribution;
+use rand::Rng;
+
+#[cfg(any(feature = "rand_0_8", feature = "rand_0_9", feature = "rand_0_10"))]
+use index::Probability;
+use {Roulette, RouletteIndex};
+
+const EMPTY: &str = "Can't sample from an empty Roulette";
+
+/// A `Distribution` over references to a `Roulette`'s elements, returned by
+/// `Roulette::elements`.
+pub struct Elements<'a, T: 'a> {
+    roulette: &'a Roulette<T>,
+}
+
+impl<'a, T> Clone for Elements<'a, T> {
+    fn clone(&self) -> Elements<'a, T> {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Elements<'a, T> {}
+
+impl<T> Roulette<T> {
+    /// Returns a `Distribution` that samples references to elements, which
+    /// unlike `Roulette` itself doesn't require them to be `Clone`.
+    pub fn elements(&self) -> Elements<'_, T> {
+        Elements { roulette: self }
+    }
+}
+
+impl<T: Clone> Distribution<T> for Roulette<T> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> T {
+        self.try_sample(rng).expect(EMPTY).clone()
+    }
+}
+
+impl<'a, T> Distribution<&'a T> for Elements<'a, T> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &'a T {
+        self.roulette.try_sample(rng).expect(EMPTY)
+    }
+}
+
+impl Distribution<usize> for RouletteIndex {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.try_sample(rng).expect(EMPTY)
+    }
+}
+
+/// The random numbers needed to sample from a `Roulette`, so that each newer
+/// version of `rand` only has to provide these.
+#[cfg(any(feature = "rand_0_8", feature = "rand_0_9", feature = "rand_0_10"))]
+trait Source {
+    /// Returns a uniformly distributed integer in `0..n`.
+    fn below(&mut self, n: usize) -> usize;
+    /// Returns a uniformly distributed integer in `0..n`.
+    fn below_u64(&mut self, n: u64) -> u64;
+    /// Returns a uniformly distributed number in `[0, 1)`.
+    fn unit(&mut self) -> f64;
+    /// Returns a uniformly distributed `u64`.
+    fn word(&mut self) -> u64;
+}
+
+#[cfg(any(feature = "rand_0_8", feature = "rand_0_9", feature = "rand_0_10"))]
+fn sample_index<S: Source>(index: &RouletteIndex, source: &mut S) -> usize {
+    assert!(!index.is_empty(), "{}", EMPTY);
+    if let Some(ref thresholds) = index.single_word {
+        return index.single_word_index(thresholds, source.word());
+    }
+    let column = source.below(index.len());
+    let coin = match index.probability {
+        Probability::Float(ref probability) => source.unit() < probability[column],
+        Probability::Exact {
+            ref numerators,
+            denominator,
+            ..
+        } => source.below_u64(denominator) < numerators[column],
+    };
+    if coin {
+        column
+    } else {
+        index.alias[column]
+    }
+}
+
+/// Implements `Distribution` for a newer version of `rand`, given the `Source`
+/// for its `Rng`s.
+#[cfg(any(feature = "rand_0_8", feature = "rand_0_9", feature = "rand_0_10"))]
+macro_rules! impl_distribution {
+    ($rand:ident, $distr:ident, $source:ident) => {
+        impl<T: Clone> $rand::$distr::Distribution<T> for Roulette<T> {
+            fn sample<R: $rand::Rng + ?Sized>(&self, rng: &mut R) -> T {
+                self.items[sample_index(&self.index, &mut $source(rng))].clone()
+            }
+        }
+
+        impl<'a, T> $rand::$distr::Distribution<&'a T> for Elements<'a, T> {
+            fn sample<R: $rand::Rng + ?Sized>(&self, rng: &mut R) -> &'a T {
+                let roulette = self.roulette;
+                &roulette.items[sample_index(&roulette.index, &mut $source(rng))]
+            }
+        }
+
+        impl $rand::$distr::Distribution<usize> for RouletteIndex {
+            fn sample<R: $rand::Rng + ?Sized>(&self, rng: &mut R) -> usize {
+                sample_index(self, &mut $source(rng))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "rand_0_8")]
+mod rand_0_8_impls {
+    use rand_0_8;
+    use rand_0_8::Rng;
+
+    use super::{sample_index, Elements, Source};
+    use {Roulette, RouletteIndex};
+
+    struct Rand08<'a, R: ?Sized + 'a>(&'a mut R);
+
+    impl<'a, R: Rng + ?Sized> Source for Rand08<'a, R> {
+        fn below(&mut self, n: usize) -> usize {
+            self.0.gen_range(0..n)
+        }
+
+        fn below_u64(&mut self, n: u64) -> u64 {
+            self.0.gen_range(0..n)
+        }
+
+        fn unit(&mut self) -> f64 {
+            self.0.gen()
+        }
+
+        fn word(&mut self) -> u64 {
+            self.0.gen()
+        }
+    }
+
+    impl_distribution!(rand_0_8, distributions, Rand08);
+}
+
+#[cfg(feature = "rand_0_9")]
+mod rand_0_9_impls {
+    use rand_0_9;
+    use rand_0_9::Rng;
+
+    use super::{sample_index, Elements, Source};
+    use {Roulette, RouletteIndex};
+
+    struct Rand09<'a, R: ?Sized + 'a>(&'a mut R);
+
+    impl<'a, R: Rng + ?Sized> Source for Rand09<'a, R> {
+        fn below(&mut self, n: usize) -> usize {
+            self.0.random_range(0..n)
+        }
+
+        fn below_u64(&mut self, n: u64) -> u64 {
+            self.0.random_range(0..n)
+        }
+
+        fn unit(&mut self) -> f64 {
+            self.0.random()
+        }
+
+        fn word(&mut self) -> u64 {
+            self.0.random()
+        }
+    }
+
+    impl_distribution!(rand_0_9, distr, Rand09);
+}
+
+#[cfg(feature = "rand_0_10")]
+mod rand_0_10_impls {
+    use rand_0_10;
+    use rand_0_10::{Rng, RngExt};
+
+    use super::{sample_index, Elements, Source};
+    use {Roulette, RouletteIndex};
+
+    struct Rand010<'a, R: ?Sized + 'a>(&'a mut R);
+
+    impl<'a, R: Rng + ?Sized> Source for Rand010<'a, R> {
+        fn below(&mut self, n: usize) -> usize {
+            self.0.random_range(0..n)
+        }
+
+        fn below_u64(&mut self, n: u64) -> u64 {
+            self.0.random_range(0..n)
+        }
+
+        fn unit(&mut self) -> f64 {
+            self.0.random()
+        }
+
+        fn word(&mut self) -> u64 {
+            self.0.random()
+        }
+    }
+
+    impl_distribution!(rand_0_10, distr, Rand010);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn check<F: FnMut(&Roulette<char>) -> (char, char, usize)>(mut sample: F) {
+        let roulette = Roulette::new(vec![('a', 0.0), ('b', 1.0), ('c', 0.0)]);
+        for _ in 0..10 {
+            assert_eq!(sample(&roulette), ('b', 'b', 1));
+        }
+        let roulette = Roulette::from_integer_weights(vec![('a', 0), ('b', 0), ('c', 3)]);
+        for _ in 0..10 {
+            assert_eq!(sample(&roulette), ('c', 'c', 2));
+        }
+    }
+
+    #[test]
+    fn rand_0_7() {
+        let mut rng = rand::thread_rng();
+        check(|roulette| {
+            (
+                rng.sample(roulette),
+                *rng.sample(roulette.elements()),
+                rng.sample(roulette.as_index()),
+            )
+        });
+        let roulette = Roulette::new(vec![('a', 0.0), ('b', 1.0)]);
+        let samples: Vec<char> = rng.sample_iter(&roulette).take(3).collect();
+        assert_eq!(samples, vec!['b'; 3]);
+        let samples: Vec<&char> = rng.sample_iter(roulette.elements()).take(3).collect();
+        assert_eq!(samples, vec![&'b'; 3]);
+    }
+
+    /// A SplitMix64 generator, so the tests don't need the `std` features of
+    /// the newer versions of `rand`.
+    #[cfg(any(feature = "rand_0_8", feature = "rand_0_9", feature = "rand_0_10"))]
+    struct SplitMix(u64);
+
+    #[cfg(any(feature = "rand_0_8", feature = "rand_0_9", feature = "rand_0_10"))]
+    impl SplitMix {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn fill(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+
+    #[cfg(feature = "rand_0_8")]
+    impl rand_0_8::RngCore for SplitMix {
+        fn next_u32(&mut self) -> u32 {
+            self.next() as u32
+        }
+        fn next_u64(&mut self) -> u64 {
+            self.next()
+        }
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.fill(dest)
+        }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_0_8::Error> {
+            self.fill(dest);
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "rand_0_9")]
+    impl rand_0_9::RngCore for SplitMix {
+        fn next_u32(&mut self) -> u32 {
+            self.next() as u32
+        }
+        fn next_u64(&mut self) -> u64 {
+            self.next()
+        }
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.fill(dest)
+        }
+    }
+
+    #[cfg(feature = "rand_0_10")]
+    impl rand_0_10::TryRng for SplitMix {
+        type Error = core::convert::Infallible;
+        fn try_next_u32(&mut self) -> Result<u32, Self::Error> {
+            Ok(self.next() as u32)
+        }
+        fn try_next_u64(&mut self) -> Result<u64, Self::Error> {
+            Ok(self.next())
+        }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Self::Error> {
+            self.fill(dest);
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "rand_0_8")]
+    #[test]
+    fn rand_0_8() {
+        use rand_0_8::Rng;
+
+        let mut rng = SplitMix(8);
+        check(|roulette| {
+            (
+                rng.sample(roulette),
+                *rng.sample(roulette.elements()),
+                rng.sample(roulette.as_index()),
+            )
+        });
+    }
+
+    #[cfg(feature = "rand_0_9")]
+    #[test]
+    fn rand_0_9() {
+        use rand_0_9::Rng;
+
+        let mut rng = SplitMix(9);
+        check(|roulette| {
+            (
+                rng.sample(roulette),
+                *rng.sample(roulette.elements()),
+                rng.sample(roulette.as_index()),
+            )
+        });
+    }
+
+    #[cfg(feature = "rand_0_10")]
+    #[test]
+    fn rand_0_10() {
+        use rand_0_10::RngExt;
+
+        let mut rng = SplitMix(10);
+        check(|roulette| {
+            (
+                rng.sample(roulette),
+                *rng.sample(roulette.elements()),
+                rng.sample(roulette.as_index()),
+            )
+        });
+    }
+}
@@ -0,0 +1,276 @@
+use alloc::vec::Vec;
+use rand::Rng;
+
+use error::{self, RouletteError};
+
+/// A variant of `Roulette` whose weights can be changed after construction.
+///
+/// The weights are stored in a binary tree where each node holds the sum of
+/// its children, so updating a weight takes O(log n) time, as does sampling.
+/// Since every node is recomputed from its children rather than adjusted by
+/// the difference, repeated updates don't accumulate rounding errors.
+///
+/// Weights are validated like in `Roulette::try_new`, except that they may all
+/// be zero (for example while they're being updated); `try_sample` returns
+/// `None` in that case.
+pub struct DynamicRoulette<T> {
+    items: Vec<T>,
+    /// The leaves are stored at `tree[capacity..capacity + items.len()]`, and
+    /// the children of node `i` are at `2 * i` and `2 * i + 1`. The root is at
+    /// index 1; index 0 is unused.
+    tree: Vec<f64>,
+}
+
+impl<T> DynamicRoulette<T> {
+    /// Creates a `DynamicRoulette` with the given weights for a set of
+    /// elements.
+    ///
+    /// Panics if the weights are invalid; see `DynamicRoulette::try_new`.
+    pub fn new(weights: Vec<(T, f64)>) -> DynamicRoulette<T> {
+        match DynamicRoulette::try_new(weights) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid weights in DynamicRoulette: {}", err),
+        }
+    }
+
+    /// Creates a `DynamicRoulette` like `DynamicRoulette::new`, but returns an
+    /// error instead of panicking if any weight is negative, NaN or infinite,
+    /// or if their sum is infinite.
+    pub fn try_new(weights: Vec<(T, f64)>) -> Result<DynamicRoulette<T>, RouletteError> {
+        for (index, weight) in weights.iter().enumerate() {
+            error::check_weight(index, weight.1)?;
+        }
+
+        let capacity = weights.len().next_power_of_two();
+        let mut tree = vec![0.0; 2 * capacity];
+        let mut items = Vec::with_capacity(weights.len());
+        for (i, (item, weight)) in weights.into_iter().enumerate() {
+            items.push(item);
+            tree[capacity + i] = weight;
+        }
+        let mut roulette = DynamicRoulette { items, tree };
+        roulette.rebuild_nodes();
+        if roulette.total_weight().is_infinite() {
+            return Err(RouletteError::SumOverflow);
+        }
+        Ok(roulette)
+    }
+
+    /// Creates a `DynamicRoulette` with no elements.
+    pub fn empty() -> DynamicRoulette<T> {
+        DynamicRoulette {
+            items: Vec::new(),
+            tree: vec![0.0; 2],
+        }
+    }
+
+    /// Returns the number of elements, including those with zero weight.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the element at `index`, or `None` if it's out of bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    /// Returns the weight of the element at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn weight(&self, index: usize) -> f64 {
+        assert!(index < self.len(), "DynamicRoulette index out of bounds");
+        self.tree[self.capacity() + index]
+    }
+
+    /// Returns the sum of all weights.
+    pub fn total_weight(&self) -> f64 {
+        self.tree[1]
+    }
+
+    /// Changes the weight of the element at `index` in O(log n) time.
+    ///
+    /// If the weight is invalid, returns an error and leaves the
+    /// `DynamicRoulette` unchanged. Panics if `index` is out of bounds.
+    pub fn set_weight(&mut self, index: usize, weight: f64) -> Result<(), RouletteError> {
+        assert!(index < self.len(), "DynamicRoulette index out of bounds");
+        error::check_weight(index, weight)?;
+        let old = self.weight(index);
+        self.set_leaf(index, weight);
+        if self.total_weight().is_infinite() {
+            self.set_leaf(index, old);
+            return Err(RouletteError::SumOverflow);
+        }
+        Ok(())
+    }
+
+    /// Adds an element with the given weight at the end, in amortized
+    /// O(log n) time.
+    ///
+    /// If the weight is invalid, returns an error and leaves the
+    /// `DynamicRoulette` unchanged.
+    pub fn push(&mut self, item: T, weight: f64) -> Result<(), RouletteError> {
+        let index = self.len();
+        error::check_weight(index, weight)?;
+        if index == self.capacity() {
+            self.grow();
+        }
+        self.set_leaf(index, weight);
+        if self.total_weight().is_infinite() {
+            self.set_leaf(index, 0.0);
+            return Err(RouletteError::SumOverflow);
+        }
+        self.items.push(item);
+        Ok(())
+    }
+
+    /// Removes the element at `index` in O(log n) time and returns it along
+    /// with its weight.
+    ///
+    /// Like `Vec::swap_remove`, the last element takes its place, so this
+    /// doesn't preserve the order of the elements. Panics if `index` is out of
+    /// bounds.
+    pub fn remove(&mut self, index: usize) -> (T, f64) {
+        assert!(index < self.len(), "DynamicRoulette index out of bounds");
+        let last = self.len() - 1;
+        let weight = self.weight(index);
+        let last_weight = self.weight(last);
+        self.set_leaf(last, 0.0);
+        if index != last {
+            self.set_leaf(index, last_weight);
+        }
+        (self.items.swap_remove(index), weight)
+    }
+
+    /// Returns a random element; each element's chance of being returned
+    /// is proportional to its current weight.
+    ///
+    /// Panics if there are no elements with a non-zero weight.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &T {
+        self.try_sample(rng)
+            .expect("Can't sample from a DynamicRoulette whose weights are all zero")
+    }
+
+    /// Returns a random element like `DynamicRoulette::sample`, or `None` if
+    /// there are no elements with a non-zero weight.
+    pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        if self.total_weight() <= 0.0 {
+            return None;
+        }
+        let mut target = rng.gen::<f64>() * self.total_weight();
+        let mut node = 1;
+        while node < self.capacity() {
+            let left = self.tree[2 * node];
+            let right = self.tree[2 * node + 1];
+            // Due to rounding, `target` can end up slightly past the total;
+            // never descend into a subtree whose weights are all zero.
+            if target < left || right == 0.0 {
+                node *= 2;
+            } else {
+                target -= left;
+                node = 2 * node + 1;
+            }
+        }
+        Some(&self.items[node - self.capacity()])
+    }
+
+    fn capacity(&self) -> usize {
+        self.tree.len() / 2
+    }
+
+    fn set_leaf(&mut self, index: usize, weight: f64) {
+        let mut node = self.capacity() + index;
+        self.tree[node] = weight;
+        while node > 1 {
+            node /= 2;
+            self.tree[node] = self.tree[2 * node] + self.tree[2 * node + 1];
+        }
+    }
+
+    fn grow(&mut self) {
+        let capacity = self.capacity();
+        let mut tree = vec![0.0; 4 * capacity];
+        tree[2 * capacity..3 * capacity].copy_from_slice(&self.tree[capacity..]);
+        self.tree = tree;
+        self.rebuild_nodes();
+    }
+
+    fn rebuild_nodes(&mut self) {
+        for node in (1..self.capacity()).rev() {
+            self.tree[node] = self.tree[2 * node] + self.tree[2 * node + 1];
+        }
+    }
+}
+
+impl<T> Default for DynamicRoulette<T> {
+    fn default() -> DynamicRoulette<T> {
+        DynamicRoulette::empty()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates() {
+        let mut rng = rand::thread_rng();
+        let mut roulette = DynamicRoulette::new(vec![('a', 1.0), ('b', 0.0), ('c', 2.0)]);
+        roulette.set_weight(0, 0.0).unwrap();
+        roulette.set_weight(2, 0.0).unwrap();
+        assert_eq!(roulette.try_sample(&mut rng), None);
+
+        roulette.set_weight(1, 3.0).unwrap();
+        for _ in 0..10 {
+            assert_eq!(&'b', roulette.sample(&mut rng));
+        }
+
+        assert_eq!(roulette.remove(1), ('b', 3.0));
+        assert_eq!(roulette.get(1), Some(&'c'));
+        assert_eq!(roulette.try_sample(&mut rng), None);
+    }
+
+    #[test]
+    fn push_from_empty() {
+        let mut rng = rand::thread_rng();
+        let mut roulette = DynamicRoulette::empty();
+        assert_eq!(roulette.try_sample(&mut rng), None);
+        for i in 0..100 {
+            roulette.push(i, 0.0).unwrap();
+        }
+        roulette.push(100, 0.5).unwrap();
+        assert_eq!(roulette.len(), 101);
+        assert_eq!(roulette.total_weight(), 0.5);
+        for _ in 0..10 {
+            assert_eq!(&100, roulette.sample(&mut rng));
+        }
+    }
+
+    #[test]
+    fn invalid_weights() {
+        let mut roulette = DynamicRoulette::new(vec![('a', 1.0), ('b', f64::MAX)]);
+        assert_eq!(
+            roulette.set_weight(0, -1.0),
+            Err(RouletteError::Negative { index: 0 })
+        );
+        assert_eq!(
+            roulette.push('c', f64::NAN),
+            Err(RouletteError::NaN { index: 2 })
+        );
+        assert_eq!(
+            roulette.set_weight(0, f64::MAX),
+            Err(RouletteError::SumOverflow)
+        );
+        assert_eq!(
+            roulette.push('c', f64::MAX),
+            Err(RouletteError::SumOverflow)
+        );
+        assert_eq!(roulette.len(), 2);
+        assert_eq!(roulette.weight(0), 1.0);
+        assert_eq!(roulette.total_weight(), 1.0 + f64::MAX);
+    }
+}
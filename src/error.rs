@@ -0,0 +1,94 @@
+use core::fmt;
+
+/// The errors that can occur when building or sampling from a `Roulette`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RouletteError {
+    /// No weights were given, where at least one is required.
+    Empty,
+    /// The weight at `index` is negative.
+    Negative { index: usize },
+    /// The weight at `index` is NaN.
+    NaN { index: usize },
+    /// The weight at `index` is infinite.
+    Infinite { index: usize },
+    /// The weights are all zero.
+    ZeroSum,
+    /// The weights are all finite, but their sum isn't.
+    SumOverflow,
+    /// More distinct elements were requested than have a non-zero weight.
+    NotEnoughItems { requested: usize, available: usize },
+    /// A softmax temperature wasn't positive and finite.
+    InvalidTemperature,
+    /// There are too many elements for a `CompactRoulette`.
+    TooManyItems { len: usize },
+}
+
+impl fmt::Display for RouletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RouletteError::Empty => write!(f, "no probabilities were given"),
+            RouletteError::Negative { index } => {
+                write!(f, "probability at index {} must not be negative", index)
+            }
+            RouletteError::NaN { index } => write!(f, "probability at index {} is NaN", index),
+            RouletteError::Infinite { index } => {
+                write!(f, "probability at index {} is infinite", index)
+            }
+            RouletteError::ZeroSum => write!(f, "probabilities must not all be zero"),
+            RouletteError::SumOverflow => write!(f, "sum of probabilities is too large"),
+            RouletteError::NotEnoughItems {
+                requested,
+                available,
+            } => write!(
+                f,
+                "requested {} distinct elements, but only {} have a non-zero probability",
+                requested, available
+            ),
+            RouletteError::InvalidTemperature => {
+                write!(f, "temperature must be positive and finite")
+            }
+            RouletteError::TooManyItems { len } => {
+                write!(f, "{} elements are more than a compact table can hold", len)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RouletteError {}
+
+/// Checks that a single weight can be used in a `Roulette`.
+pub(crate) fn check_weight(index: usize, weight: f64) -> Result<(), RouletteError> {
+    if weight.is_nan() {
+        Err(RouletteError::NaN { index })
+    } else if weight.is_infinite() {
+        Err(RouletteError::Infinite { index })
+    } else if weight < 0.0 {
+        Err(RouletteError::Negative { index })
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks a whole set of weights, returning their sum.
+pub(crate) fn check_weights<I>(weights: I) -> Result<f64, RouletteError>
+where
+    I: IntoIterator<Item = f64>,
+{
+    let mut len = 0;
+    let mut sum = 0.0;
+    for (index, weight) in weights.into_iter().enumerate() {
+        check_weight(index, weight)?;
+        sum += weight;
+        len += 1;
+    }
+    if len == 0 {
+        Err(RouletteError::Empty)
+    } else if sum == 0.0 {
+        Err(RouletteError::ZeroSum)
+    } else if sum.is_infinite() {
+        Err(RouletteError::SumOverflow)
+    } else {
+        Ok(sum)
+    }
+}
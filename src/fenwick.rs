@@ -0,0 +1,360 @@
+use alloc::vec::Vec;
+use rand::Rng;
+
+use error::{self, RouletteError};
+
+/// A variant of `Roulette` backed by a Fenwick tree (binary indexed tree), so
+/// that weights can be updated in O(log n) time and prefix sums of the
+/// weights can be queried, at the cost of O(log n) sampling.
+///
+/// It's built and validated like `Roulette::try_new`. Updated weights are
+/// validated the same way, except that they may all become zero; `try_sample`
+/// returns `None` in that case.
+///
+/// Like in `DynamicRoulette`, every node touched by an update is recomputed
+/// from the weights below it, in the same order as when the tree is built,
+/// rather than adjusted by the difference. So repeated updates don't
+/// accumulate rounding errors, and a range whose weights are all zero sums to
+/// exactly zero. This takes about twice as much memory as a plain Fenwick tree.
+pub struct FenwickRoulette<T> {
+    items: Vec<T>,
+    /// Node `j`, for `j` from 1 to n, holds the sum of the weights in
+    /// `j - lowbit(j)..j`, where `lowbit(j)` is the lowest set bit of `j`.
+    /// It's built up from partial sums: the first is the weight at `j - 1`,
+    /// and each next one adds the node that covers the range before it, so
+    /// the sums cover 1, 2, 4, ..., `lowbit(j)` weights. The partial sums of
+    /// node `j` start at `offset(j)`, and the last one is the node's value.
+    sums: Vec<f64>,
+}
+
+fn lowbit(j: usize) -> usize {
+    j & j.wrapping_neg()
+}
+
+/// Returns where the partial sums of node `j` start: node `i` has
+/// `i.trailing_zeros() + 1` of them, and the trailing zeros of `1..m` add up
+/// to `m - m.count_ones()`.
+fn offset(j: usize) -> usize {
+    2 * (j - 1) - (j - 1).count_ones() as usize
+}
+
+impl<T> FenwickRoulette<T> {
+    /// Creates a `FenwickRoulette` with the given probabilities for a set of
+    /// elements; see `Roulette::new`.
+    ///
+    /// Panics if the probabilities are invalid; see `FenwickRoulette::try_new`.
+    pub fn new(items: Vec<(T, f64)>) -> FenwickRoulette<T> {
+        match FenwickRoulette::try_new(items) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `FenwickRoulette` like `FenwickRoulette::new`, but returns an
+    /// error instead of panicking if the probabilities are all zero, or if any
+    /// are negative, NaN or infinite.
+    ///
+    /// An empty `Vec` gives an empty `FenwickRoulette`.
+    pub fn try_new(items: Vec<(T, f64)>) -> Result<FenwickRoulette<T>, RouletteError> {
+        if items.is_empty() {
+            return Ok(FenwickRoulette::empty());
+        }
+        error::check_weights(items.iter().map(|item| item.1))?;
+        let len = items.len();
+        let mut sums = vec![0.0; offset(len + 1)];
+        let mut roulette_items = Vec::with_capacity(len);
+        for (i, (item, weight)) in items.into_iter().enumerate() {
+            roulette_items.push(item);
+            sums[offset(i + 1)] = weight;
+        }
+        let mut roulette = FenwickRoulette {
+            items: roulette_items,
+            sums,
+        };
+        for j in 1..=len {
+            roulette.update_node(j, 1);
+        }
+        Ok(roulette)
+    }
+
+    /// Creates a `FenwickRoulette` with no elements.
+    pub fn empty() -> FenwickRoulette<T> {
+        FenwickRoulette {
+            items: Vec::new(),
+            sums: Vec::new(),
+        }
+    }
+
+    /// Returns the number of elements, including those with zero weight.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the elements, in the order they were given.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns the weight of the element at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn weight(&self, index: usize) -> f64 {
+        assert!(index < self.len(), "FenwickRoulette index out of bounds");
+        self.sums[offset(index + 1)]
+    }
+
+    /// Returns the sum of all weights.
+    pub fn total_weight(&self) -> f64 {
+        self.prefix_sum(self.len())
+    }
+
+    /// Returns the sum of the weights of the elements before `end`, in
+    /// O(log n) time.
+    ///
+    /// Panics if `end` is greater than the number of elements.
+    pub fn prefix_sum(&self, end: usize) -> f64 {
+        assert!(end <= self.len(), "FenwickRoulette index out of bounds");
+        let mut sum = 0.0;
+        let mut i = end;
+        while i > 0 {
+            sum += self.node(i);
+            i -= lowbit(i);
+        }
+        sum
+    }
+
+    /// Returns the index of the element whose range of cumulative weight
+    /// contains `target`: the `i` for which `prefix_sum(i) <= target <
+    /// prefix_sum(i + 1)`, in O(log n) time. Returns `None` if `target` is
+    /// negative or at least the total weight.
+    pub fn search(&self, target: f64) -> Option<usize> {
+        if target.is_nan() || target < 0.0 || target >= self.total_weight() {
+            return None;
+        }
+        let mut position = 0;
+        let mut remaining = target;
+        let mut step = if self.is_empty() {
+            0
+        } else {
+            1 << (usize::BITS - 1 - self.len().leading_zeros())
+        };
+        while step > 0 {
+            let next = position + step;
+            if next <= self.len() && self.node(next) <= remaining {
+                position = next;
+                remaining -= self.node(next);
+            }
+            step /= 2;
+        }
+        // Subtracting the partial sums can round down enough to step past the
+        // last element.
+        Some(position.min(self.len() - 1))
+    }
+
+    /// Changes the weight of the element at `index` in O(log n) time.
+    ///
+    /// If the weight is invalid, returns an error and leaves the
+    /// `FenwickRoulette` unchanged. Panics if `index` is out of bounds.
+    pub fn update(&mut self, index: usize, weight: f64) -> Result<(), RouletteError> {
+        assert!(index < self.len(), "FenwickRoulette index out of bounds");
+        error::check_weight(index, weight)?;
+        let old = self.weight(index);
+        self.set_weight(index, weight);
+        if self.total_weight().is_infinite() {
+            self.set_weight(index, old);
+            return Err(RouletteError::SumOverflow);
+        }
+        Ok(())
+    }
+
+    /// Returns a random element; each element's chance of being returned
+    /// is proportional to its current weight.
+    ///
+    /// Panics if there are no elements with a non-zero weight.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &T {
+        self.try_sample(rng)
+            .expect("Can't sample from a FenwickRoulette whose weights are all zero")
+    }
+
+    /// Returns a random element like `FenwickRoulette::sample`, or `None` if
+    /// there are no elements with a non-zero weight.
+    pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        self.try_sample_index(rng).map(|index| &self.items[index])
+    }
+
+    /// Returns the index of a random element, chosen like in
+    /// `FenwickRoulette::sample`.
+    ///
+    /// Panics if there are no elements with a non-zero weight.
+    pub fn sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.try_sample_index(rng)
+            .expect("Can't sample from a FenwickRoulette whose weights are all zero")
+    }
+
+    /// Returns the index of a random element like
+    /// `FenwickRoulette::sample_index`, or `None` if there are no elements
+    /// with a non-zero weight.
+    pub fn try_sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+        let total = self.total_weight();
+        if total <= 0.0 {
+            return None;
+        }
+        let target = rng.gen::<f64>() * total;
+        match self.search(target) {
+            Some(index) if self.weight(index) > 0.0 => Some(index),
+            // Due to rounding, the target can end up at or past the total, or
+            // on an element whose weight is zero. That's rare enough to settle
+            // with a linear scan.
+            _ => Some(self.scan(target)),
+        }
+    }
+
+    /// Returns the value of node `j`.
+    fn node(&self, j: usize) -> f64 {
+        self.sums[offset(j) + j.trailing_zeros() as usize]
+    }
+
+    /// Recomputes the partial sums of node `j` from the `from`th on, from the
+    /// ones before them.
+    fn update_node(&mut self, j: usize, from: u32) {
+        let start = offset(j);
+        for level in from..=j.trailing_zeros() {
+            let level = level as usize;
+            self.sums[start + level] =
+                self.sums[start + level - 1] + self.node(j - (1 << (level - 1)));
+        }
+    }
+
+    /// Sets a weight and recomputes the nodes that cover it. Each parent only
+    /// needs the partial sums that include its child recomputed, so this
+    /// takes O(log n) time in total.
+    fn set_weight(&mut self, index: usize, weight: f64) {
+        let mut j = index + 1;
+        self.sums[offset(j)] = weight;
+        let mut from = 1;
+        while j <= self.len() {
+            self.update_node(j, from);
+            from = j.trailing_zeros() + 1;
+            j += lowbit(j);
+        }
+    }
+
+    /// Returns the first element with a non-zero weight whose cumulative
+    /// weight is greater than `target`, or the last one if there's none.
+    fn scan(&self, target: f64) -> usize {
+        let mut sum = 0.0;
+        let mut chosen = 0;
+        for index in 0..self.len() {
+            let weight = self.weight(index);
+            if weight > 0.0 {
+                sum += weight;
+                chosen = index;
+                if sum > target {
+                    break;
+                }
+            }
+        }
+        chosen
+    }
+}
+
+impl<T> Default for FenwickRoulette<T> {
+    fn default() -> FenwickRoulette<T> {
+        FenwickRoulette::empty()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use stats::tests::{assert_fits, WEIGHTS};
+
+    #[test]
+    fn prefix_sums() {
+        let roulette = FenwickRoulette::new(WEIGHTS.iter().map(|&weight| ((), weight)).collect());
+        let mut sum = 0.0;
+        for (i, &weight) in WEIGHTS.iter().enumerate() {
+            assert_eq!(roulette.prefix_sum(i), sum);
+            if weight > 0.0 {
+                assert_eq!(roulette.search(sum), Some(i));
+            }
+            sum += weight;
+        }
+        assert_eq!(roulette.total_weight(), sum);
+        assert_eq!(roulette.search(sum), None);
+        assert_eq!(roulette.search(-1.0), None);
+        assert_fits(21, |rng| roulette.sample_index(rng));
+    }
+
+    #[test]
+    fn updates() {
+        let mut rng = rand::thread_rng();
+        let mut roulette = FenwickRoulette::new(vec![('a', 1.0), ('b', 0.0), ('c', 2.0)]);
+        roulette.update(0, 0.0).unwrap();
+        roulette.update(2, 0.0).unwrap();
+        assert_eq!(roulette.try_sample(&mut rng), None);
+
+        roulette.update(1, 3.0).unwrap();
+        assert_eq!(roulette.prefix_sum(2), 3.0);
+        for _ in 0..10 {
+            assert_eq!(&'b', roulette.sample(&mut rng));
+        }
+
+        // After any updates, the tree matches one built from scratch.
+        let mut roulette = FenwickRoulette::new((0..100).map(|i| (i, 1.0)).collect());
+        for i in 0..1000 {
+            roulette.update(i % 100, (i % 7) as f64 * 0.1).unwrap();
+        }
+        let weights = (0..100).map(|i| (i, roulette.weight(i))).collect();
+        assert_eq!(roulette.sums, FenwickRoulette::new(weights).sums);
+    }
+
+    #[test]
+    fn zeroed_weights() {
+        let mut rng = StdRng::seed_from_u64(21);
+        let mut weights: Vec<((), f64)> = (0..16).map(|_| ((), rng.gen::<f64>())).collect();
+        weights[15].1 = 1e-20;
+        let mut roulette = FenwickRoulette::new(weights);
+        for i in 0..15 {
+            roulette.update(i, 0.0).unwrap();
+        }
+        assert_eq!(roulette.total_weight(), 1e-20);
+        for end in 0..16 {
+            assert_eq!(roulette.prefix_sum(end), 0.0);
+        }
+        for _ in 0..100 {
+            assert_eq!(roulette.sample_index(&mut rng), 15);
+        }
+    }
+
+    #[test]
+    fn invalid_weights() {
+        assert_eq!(
+            FenwickRoulette::try_new(vec![('a', 0.0), ('b', 0.0)]).err(),
+            Some(RouletteError::ZeroSum)
+        );
+        assert_eq!(
+            FenwickRoulette::try_new(vec![('a', 1.0), ('b', -1.0)]).err(),
+            Some(RouletteError::Negative { index: 1 })
+        );
+        let mut roulette = FenwickRoulette::new(vec![('a', 1.0), ('b', f64::MAX)]);
+        assert_eq!(
+            roulette.update(0, f64::NAN),
+            Err(RouletteError::NaN { index: 0 })
+        );
+        assert_eq!(
+            roulette.update(0, f64::MAX),
+            Err(RouletteError::SumOverflow)
+        );
+        assert_eq!(roulette.weight(0), 1.0);
+        assert!(FenwickRoulette::<char>::new(Vec::new()).is_empty());
+    }
+}
@@ -0,0 +1,425 @@
+//! A stable binary file format for alias tables, and with the `mmap` feature,
+//! sampling from a table in a memory-mapped file without loading it.
+//!
+//! A file holds a `RouletteIndex` but not the elements. All numbers are
+//! little-endian:
+//!
+//! | Offset | Size | Contents |
+//! |--------|------|----------|
+//! | 0 | 8 | The magic bytes `ROULETTE` |
+//! | 8 | 4 | The format version, 1 or 2 (`u32`) |
+//! | 12 | 4 | 0 for a table built from `f64` weights, 1 for integer weights (`u32`) |
+//! | 16 | 8 | The number of indices, `n` (`u64`) |
+//! | 24 | 8 | For integer weights, the denominator of the coin probabilities; otherwise 0 (`u64`) |
+//! | 32 | 8n | The alias of each column (`u64`) |
+//! | 32 + 8n | 8n | The coin probability of each column (`f64`), or its numerator (`u64`) |
+//! | 32 + 16n | 8 | In version 2 only, the number of underflowed weights, `m` (`u64`) |
+//! | 40 + 16n | 16m | The index (`u64`) and weight (`f64`) of each underflowed weight |
+//!
+//! Underflowed weights are those too small compared to the sum to have a
+//! non-zero probability, which `RouletteIndex::sample_distinct` can still
+//! draw. Version 2 is only written for tables that have any, so that other
+//! tables can still be read by older versions of this crate.
+//!
+//! Tables are validated when they're read, so that a corrupted file can't make
+//! sampling index out of bounds.
+
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+use index::{Probability, StoredProbability};
+use RouletteIndex;
+
+const MAGIC: &[u8; 8] = b"ROULETTE";
+const VERSION: u32 = 1;
+const UNDERFLOW_VERSION: u32 = 2;
+const HEADER: usize = 32;
+const FLOAT: u32 = 0;
+const EXACT: u32 = 1;
+
+impl RouletteIndex {
+    /// Writes the alias table in the format described in the `file` module.
+    /// The writer is buffered internally.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(writer);
+        let (kind, denominator) = match self.probability {
+            Probability::Float(_) => (FLOAT, 0),
+            Probability::Exact { denominator, .. } => (EXACT, denominator),
+        };
+        let version = if self.underflow.is_empty() {
+            VERSION
+        } else {
+            UNDERFLOW_VERSION
+        };
+        writer.write_all(MAGIC)?;
+        writer.write_all(&version.to_le_bytes())?;
+        writer.write_all(&kind.to_le_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        writer.write_all(&denominator.to_le_bytes())?;
+        for &alias in &self.alias {
+            writer.write_all(&(alias as u64).to_le_bytes())?;
+        }
+        match self.probability {
+            Probability::Float(ref probability) => {
+                for &p in probability {
+                    writer.write_all(&p.to_le_bytes())?;
+                }
+            }
+            Probability::Exact { ref numerators, .. } => {
+                for &numerator in numerators {
+                    writer.write_all(&numerator.to_le_bytes())?;
+                }
+            }
+        }
+        if version == UNDERFLOW_VERSION {
+            writer.write_all(&(self.underflow.len() as u64).to_le_bytes())?;
+            for &(index, weight) in &self.underflow {
+                writer.write_all(&(index as u64).to_le_bytes())?;
+                writer.write_all(&weight.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Reads an alias table written by `RouletteIndex::write_to`.
+    ///
+    /// Returns an error with the kind `InvalidData` if the table isn't valid.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<RouletteIndex> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let table = Table::parse(&bytes)?;
+        let alias = (0..table.len).map(|i| table.alias(&bytes, i)).collect();
+        let probability = match table.denominator {
+            None => StoredProbability::Float(
+                (0..table.len)
+                    .map(|i| f64::from_bits(table.coin(&bytes, i)))
+                    .collect(),
+            ),
+            Some(denominator) => StoredProbability::Exact {
+                numerators: (0..table.len).map(|i| table.coin(&bytes, i)).collect(),
+                denominator,
+            },
+        };
+        let underflow = (0..table.underflow)
+            .map(|i| table.underflow(&bytes, i))
+            .collect();
+        RouletteIndex::from_parts(alias, probability, underflow).map_err(invalid)
+    }
+}
+
+/// The header of a table, for reading its columns from the bytes.
+struct Table {
+    len: usize,
+    /// `None` for a table built from `f64` weights.
+    denominator: Option<u64>,
+    /// The number of underflowed weights after the columns.
+    underflow: usize,
+}
+
+impl Table {
+    /// Checks the header. The columns are validated separately, by
+    /// `RouletteIndex::from_parts` or in place for a mapped file.
+    fn parse(bytes: &[u8]) -> io::Result<Table> {
+        if bytes.len() < HEADER || &bytes[..8] != MAGIC {
+            return Err(invalid("Not a Roulette file"));
+        }
+        let version = read_u32(bytes, 8);
+        if version != VERSION && version != UNDERFLOW_VERSION {
+            return Err(invalid("Unsupported Roulette file version"));
+        }
+        let kind = read_u32(bytes, 12);
+        let len = read_u64(bytes, 16);
+        let denominator = read_u64(bytes, 24);
+        let columns = len
+            .checked_mul(16)
+            .and_then(|size| size.checked_add(HEADER as u64));
+        let mut underflow = 0;
+        let mut size = columns;
+        if version == UNDERFLOW_VERSION {
+            match columns {
+                Some(columns) if columns <= bytes.len() as u64 - 8 => {
+                    underflow = read_u64(bytes, columns as usize);
+                    size = underflow
+                        .checked_mul(16)
+                        .and_then(|size| size.checked_add(columns + 8));
+                }
+                _ => size = None,
+            }
+        }
+        if size != Some(bytes.len() as u64) {
+            return Err(invalid("Roulette file has the wrong length"));
+        }
+        let len = len as usize;
+        let denominator = match kind {
+            FLOAT => None,
+            EXACT => Some(denominator),
+            _ => return Err(invalid("Unknown kind of Roulette table")),
+        };
+        Ok(Table {
+            len,
+            denominator,
+            underflow: underflow as usize,
+        })
+    }
+
+    fn alias(&self, bytes: &[u8], column: usize) -> usize {
+        read_u64(bytes, HEADER + 8 * column) as usize
+    }
+
+    /// Returns the bits of the column's probability, or its numerator.
+    fn coin(&self, bytes: &[u8], column: usize) -> u64 {
+        read_u64(bytes, HEADER + 8 * (self.len + column))
+    }
+
+    /// Returns the `i`th underflowed weight and its index.
+    fn underflow(&self, bytes: &[u8], i: usize) -> (usize, f64) {
+        let offset = HEADER + 16 * self.len + 8 + 16 * i;
+        (
+            read_u64(bytes, offset) as usize,
+            f64::from_bits(read_u64(bytes, offset + 8)),
+        )
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(word)
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(feature = "mmap")]
+pub use self::mapped::MappedRouletteIndex;
+
+#[cfg(feature = "mmap")]
+mod mapped {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    use memmap2::Mmap;
+    use rand::distributions::{Distribution, Uniform};
+    use rand::Rng;
+
+    use super::{invalid, Table};
+    use index;
+
+    /// A `RouletteIndex` that samples directly from a memory-mapped file, so
+    /// that processes using the same file share one copy of the table.
+    pub struct MappedRouletteIndex {
+        map: Mmap,
+        table: Table,
+        /// `None` if and only if the table is empty, like in `RouletteIndex`.
+        range: Option<Uniform<usize>>,
+        coin: Option<Uniform<u64>>,
+    }
+
+    impl MappedRouletteIndex {
+        /// Maps the file at `path`, which must have been written by
+        /// `RouletteIndex::write_to`, and validates it.
+        ///
+        /// Returns an error with the kind `InvalidData` if the table isn't
+        /// valid.
+        ///
+        /// The file must not be modified or truncated while it's mapped;
+        /// doing so can make sampling return wrong indices, or crash the
+        /// process if the file gets shorter.
+        pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedRouletteIndex> {
+            let file = File::open(path)?;
+            // Safety: the file is only read, and modifying it while it's
+            // mapped is documented as unsupported.
+            let map = unsafe { Mmap::map(&file)? };
+            let table = Table::parse(&map)?;
+            validate(&table, &map)?;
+            let range = if table.len == 0 {
+                None
+            } else {
+                Some(Uniform::from(0..table.len))
+            };
+            let coin = table
+                .denominator
+                .map(|denominator| Uniform::from(0..denominator));
+            Ok(MappedRouletteIndex {
+                map,
+                table,
+                range,
+                coin,
+            })
+        }
+
+        /// Returns the number of indices, including those with zero
+        /// probability.
+        pub fn len(&self) -> usize {
+            self.table.len
+        }
+
+        /// Returns true if the table has no indices.
+        pub fn is_empty(&self) -> bool {
+            self.table.len == 0
+        }
+
+        /// Returns a random index, like `RouletteIndex::sample`.
+        ///
+        /// Panics if the table is empty.
+        pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+            self.try_sample(rng)
+                .expect("Can't sample from an empty Roulette")
+        }
+
+        /// Returns a random index like `MappedRouletteIndex::sample`, or
+        /// `None` if the table is empty.
+        pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+            let column = self.range.as_ref()?.sample(rng);
+            let coin = self.table.coin(&self.map, column);
+            let heads = match self.coin {
+                None => rng.gen::<f64>() < f64::from_bits(coin),
+                Some(ref uniform) => uniform.sample(rng) < coin,
+            };
+            Some(if heads {
+                column
+            } else {
+                self.table.alias(&self.map, column)
+            })
+        }
+    }
+
+    /// Validates the columns of a mapped table in place, with the same checks
+    /// as `RouletteIndex::from_parts`.
+    fn validate(table: &Table, bytes: &[u8]) -> io::Result<()> {
+        let columns = 0..table.len;
+        index::check_alias(table.len, columns.clone().map(|i| table.alias(bytes, i)))
+            .and_then(|()| {
+                let underflow = (0..table.underflow).map(|i| table.underflow(bytes, i));
+                index::check_underflow(table.len, underflow)
+            })
+            .and_then(|()| match table.denominator {
+                None => index::check_probabilities(
+                    columns.map(|i| f64::from_bits(table.coin(bytes, i))),
+                ),
+                Some(denominator) => {
+                    index::check_numerators(columns.map(|i| table.coin(bytes, i)), denominator)
+                }
+            })
+            .map_err(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    fn to_bytes(index: &RouletteIndex) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let weights = [3.0, 0.0, 1.0, 0.5];
+        let index = RouletteIndex::new(&weights);
+        let bytes = to_bytes(&index);
+        assert_eq!(bytes.len(), HEADER + 16 * weights.len());
+        let read = RouletteIndex::read_from(&bytes[..]).unwrap();
+        assert_eq!(
+            read.effective_probabilities(),
+            index.effective_probabilities()
+        );
+
+        let index = RouletteIndex::from_integer_weights(&[1, 0, 3]);
+        let read = RouletteIndex::read_from(&to_bytes(&index)[..]).unwrap();
+        assert_eq!(read.effective_probabilities(), vec![0.25, 0.0, 0.75]);
+
+        let read = RouletteIndex::read_from(&to_bytes(&RouletteIndex::empty())[..]).unwrap();
+        assert!(read.is_empty());
+
+        // Only a table with underflowed weights needs version 2.
+        let index = RouletteIndex::new(&[1e300, 0.0, 1e-300]);
+        let bytes = to_bytes(&index);
+        assert_eq!(read_u32(&bytes, 8), UNDERFLOW_VERSION);
+        assert_eq!(bytes.len(), HEADER + 16 * 3 + 8 + 16);
+        let read = RouletteIndex::read_from(&bytes[..]).unwrap();
+        assert_eq!(read.underflow, vec![(2, 1e-300)]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let bytes = to_bytes(&RouletteIndex::new(&[1.0, 2.0, 3.0]));
+        let check = |bytes: &[u8], message: &str| {
+            let err = RouletteIndex::read_from(bytes).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), message);
+        };
+        check(
+            &bytes[..bytes.len() - 1],
+            "Roulette file has the wrong length",
+        );
+        check(b"not a table", "Not a Roulette file");
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER] = 3;
+        check(&corrupted, "Roulette alias table is out of bounds");
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER + 24..HEADER + 32].copy_from_slice(&f64::NAN.to_le_bytes());
+        check(&corrupted, "Roulette probabilities must be between 0 and 1");
+        let mut corrupted = bytes.clone();
+        corrupted[8] = 3;
+        check(&corrupted, "Unsupported Roulette file version");
+        let mut corrupted = bytes;
+        corrupted[8] = 2;
+        check(&corrupted, "Roulette file has the wrong length");
+
+        let bytes = to_bytes(&RouletteIndex::new(&[1e300, 1e-300]));
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER + 40..HEADER + 48].copy_from_slice(&2u64.to_le_bytes());
+        check(&corrupted, "Roulette underflowed weights are invalid");
+        check(
+            &bytes[..bytes.len() - 16],
+            "Roulette file has the wrong length",
+        );
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped() {
+        use std::fs;
+
+        let path = std::env::temp_dir().join(format!("roulette-{}.bin", std::process::id()));
+        RouletteIndex::from_integer_weights(&[0, 2, 0, 1])
+            .write_to(fs::File::create(&path).unwrap())
+            .unwrap();
+        let mapped = MappedRouletteIndex::open(&path);
+        fs::remove_file(&path).unwrap();
+        let mapped = mapped.unwrap();
+        assert_eq!(mapped.len(), 4);
+
+        let mut rng = rand::thread_rng();
+        let mut counts = [0; 4];
+        for _ in 0..30_000 {
+            counts[mapped.sample(&mut rng)] += 1;
+        }
+        assert_eq!((counts[0], counts[2]), (0, 0));
+        assert!(counts[1] > 19_000 && counts[1] < 21_000);
+
+        let mut corrupted = to_bytes(&RouletteIndex::new(&[1.0, 2.0, 3.0]));
+        corrupted[HEADER] = 3;
+        fs::write(&path, &corrupted).unwrap();
+        let err = MappedRouletteIndex::open(&path).err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            err.unwrap().to_string(),
+            "Roulette alias table is out of bounds"
+        );
+    }
+}
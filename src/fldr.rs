@@ -0,0 +1,275 @@
+use alloc::vec::Vec;
+use rand::Rng;
+
+use error::RouletteError;
+
+/// An entropy-optimal variant of `Roulette` for integer weights, using the
+/// Fast Loaded Dice Roller algorithm (Saad et al., 2020).
+///
+/// Rather than drawing whole `u64`s, it walks a Knuth–Yao style tree one
+/// random bit at a time, so each sample consumes on average less than H + 6
+/// bits, where H is the Shannon entropy of the distribution, rather than the
+/// 128 bits `Roulette::sample` uses. This is worthwhile when random bits are
+/// expensive, such as with a hardware RNG. Bits are drawn through a
+/// `RandomBits`, which counts them.
+///
+/// Each element is returned with a probability of exactly its weight divided
+/// by the sum of the weights. The tree takes O(n log m) space, where m is the
+/// sum of the weights.
+pub struct FldrRoulette<T> {
+    items: Vec<T>,
+    /// For each level of the tree, the rows whose weight has a 1 in the
+    /// corresponding bit, from the most significant; a row of `items.len()`
+    /// stands for rejection. Level `i` is `rows[levels[i]..levels[i + 1]]`.
+    rows: Vec<usize>,
+    levels: Vec<usize>,
+}
+
+/// A source of random bits for `FldrRoulette`, which draws `u64`s from an
+/// `Rng` and hands them out a bit at a time, counting how many were used.
+pub struct RandomBits<R> {
+    rng: R,
+    word: u64,
+    available: u32,
+    consumed: u64,
+}
+
+impl<R: Rng> RandomBits<R> {
+    /// Creates a `RandomBits` drawing from `rng`, which may be a `&mut R`.
+    pub fn new(rng: R) -> RandomBits<R> {
+        RandomBits {
+            rng,
+            word: 0,
+            available: 0,
+            consumed: 0,
+        }
+    }
+
+    /// Returns the next random bit.
+    pub fn bit(&mut self) -> bool {
+        if self.available == 0 {
+            self.word = self.rng.next_u64();
+            self.available = 64;
+        }
+        let bit = self.word & 1 == 1;
+        self.word >>= 1;
+        self.available -= 1;
+        self.consumed += 1;
+        bit
+    }
+
+    /// Returns the number of bits returned by `RandomBits::bit` so far. Up to
+    /// 63 more bits may have been drawn from the `Rng` and not used yet.
+    pub fn bits_consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// Returns the `Rng`, discarding any bits that were drawn but not used.
+    pub fn into_inner(self) -> R {
+        self.rng
+    }
+}
+
+impl<T> FldrRoulette<T> {
+    /// Creates a `FldrRoulette` from integer weights; each element's chance
+    /// of being returned is exactly its weight divided by the sum.
+    ///
+    /// Panics if the weights are invalid; see `FldrRoulette::try_new`.
+    pub fn new(items: Vec<(T, u64)>) -> FldrRoulette<T> {
+        match FldrRoulette::try_new(items) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid weights in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `FldrRoulette` like `FldrRoulette::new`, but returns an error
+    /// instead of panicking if the weights are all zero or if their sum
+    /// doesn't fit in a `u64`.
+    ///
+    /// An empty `Vec` gives an empty `FldrRoulette`.
+    pub fn try_new(items: Vec<(T, u64)>) -> Result<FldrRoulette<T>, RouletteError> {
+        let (items, weights): (Vec<T>, Vec<u64>) = items.into_iter().unzip();
+        if items.is_empty() {
+            return Ok(FldrRoulette {
+                items,
+                rows: Vec::new(),
+                levels: vec![0],
+            });
+        }
+        let sum = weights
+            .iter()
+            .try_fold(0u64, |sum, &weight| sum.checked_add(weight))
+            .ok_or(RouletteError::SumOverflow)?;
+        if sum == 0 {
+            return Err(RouletteError::ZeroSum);
+        }
+
+        let mut rows = Vec::new();
+        let mut levels = vec![0];
+        if weights.iter().filter(|&&weight| weight > 0).count() == 1 {
+            // A single element with a non-zero weight needs no bits to choose.
+            rows.extend(weights.iter().position(|&weight| weight > 0));
+            return Ok(FldrRoulette {
+                items,
+                rows,
+                levels,
+            });
+        }
+
+        // The tree has one level per bit of 2^depth >= sum, with the
+        // difference going to a rejection row so the tree is complete.
+        let depth = 64 - (sum - 1).leading_zeros();
+        let reject = ((1u128 << depth) - u128::from(sum)) as u64;
+        for bit in (0..depth).rev() {
+            for (row, &weight) in weights.iter().enumerate() {
+                if weight >> bit & 1 == 1 {
+                    rows.push(row);
+                }
+            }
+            if reject >> bit & 1 == 1 {
+                rows.push(items.len());
+            }
+            levels.push(rows.len());
+        }
+        Ok(FldrRoulette {
+            items,
+            rows,
+            levels,
+        })
+    }
+
+    /// Returns the number of elements, including those with zero weight.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the `FldrRoulette` has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the elements, in the order they were given.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns a random element, drawing random bits from `bits`.
+    ///
+    /// Panics if the `FldrRoulette` is empty.
+    pub fn sample<R: Rng>(&self, bits: &mut RandomBits<R>) -> &T {
+        &self.items[self.sample_index(bits)]
+    }
+
+    /// Returns a random element like `FldrRoulette::sample`, or `None` if the
+    /// `FldrRoulette` is empty.
+    pub fn try_sample<R: Rng>(&self, bits: &mut RandomBits<R>) -> Option<&T> {
+        self.try_sample_index(bits).map(|index| &self.items[index])
+    }
+
+    /// Returns the index of a random element, chosen like in
+    /// `FldrRoulette::sample`.
+    ///
+    /// Panics if the `FldrRoulette` is empty.
+    pub fn sample_index<R: Rng>(&self, bits: &mut RandomBits<R>) -> usize {
+        self.try_sample_index(bits)
+            .expect("Can't sample from an empty Roulette")
+    }
+
+    /// Returns the index of a random element like
+    /// `FldrRoulette::sample_index`, or `None` if the `FldrRoulette` is empty.
+    pub fn try_sample_index<R: Rng>(&self, bits: &mut RandomBits<R>) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        if self.levels.len() == 1 {
+            return Some(self.rows[0]);
+        }
+        // `node` is the position of the current node among the internal nodes
+        // and leaves at `level`, with the leaves first.
+        let mut node = 0;
+        let mut level = 0;
+        loop {
+            node = 2 * node + bits.bit() as usize;
+            let leaves = &self.rows[self.levels[level]..self.levels[level + 1]];
+            if node < leaves.len() {
+                let row = leaves[node];
+                if row < self.len() {
+                    return Some(row);
+                }
+                node = 0;
+                level = 0;
+            } else {
+                node -= leaves.len();
+                level += 1;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use stats;
+
+    fn entropy(weights: &[u64]) -> f64 {
+        let sum: u64 = weights.iter().sum();
+        weights
+            .iter()
+            .filter(|&&weight| weight > 0)
+            .map(|&weight| {
+                let p = weight as f64 / sum as f64;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn sampling() {
+        let mut rng = StdRng::seed_from_u64(24);
+        for weights in &[
+            vec![1, 2, 3, 4, 5],
+            vec![5, 0, 1, 1, 1],
+            vec![1, 999],
+            vec![3, 3],
+        ] {
+            let roulette = FldrRoulette::new(weights.iter().map(|&weight| ((), weight)).collect());
+            let mut bits = RandomBits::new(&mut rng);
+            let samples = 100_000;
+            let indices = (0..samples).map(|_| roulette.sample_index(&mut bits));
+            let counts = stats::count_indices(weights.len(), indices);
+            let expected: Vec<f64> = weights.iter().map(|&weight| weight as f64).collect();
+            assert!(stats::chi_squared(&counts, &expected).p_value > 0.001);
+
+            let per_sample = bits.bits_consumed() as f64 / samples as f64;
+            assert!(per_sample >= entropy(weights) - 0.05);
+            assert!(per_sample < entropy(weights) + 6.0);
+        }
+    }
+
+    #[test]
+    fn bits_consumed() {
+        let mut rng = StdRng::seed_from_u64(24);
+        let mut bits = RandomBits::new(&mut rng);
+        let fair = FldrRoulette::new(vec![('a', 1), ('b', 1), ('c', 2)]);
+        for _ in 0..100 {
+            fair.sample(&mut bits);
+        }
+        // Two coin flips at most, and exactly one for 'c'.
+        assert!(bits.bits_consumed() >= 100 && bits.bits_consumed() <= 200);
+
+        let certain = FldrRoulette::new(vec![('a', 0), ('b', 7)]);
+        let before = bits.bits_consumed();
+        assert_eq!(certain.sample(&mut bits), &'b');
+        assert_eq!(bits.bits_consumed(), before);
+
+        let empty: FldrRoulette<char> = FldrRoulette::new(Vec::new());
+        assert_eq!(empty.try_sample(&mut bits), None);
+        assert_eq!(
+            FldrRoulette::try_new(vec![('a', u64::MAX), ('b', 1)]).err(),
+            Some(RouletteError::SumOverflow)
+        );
+    }
+}
@@ -0,0 +1,725 @@
+use alloc::vec::Vec;
+use rand::distributions::{Distribution, Uniform};
+use rand::Rng;
+
+use dynamic::DynamicRoulette;
+use error::{self, RouletteError};
+
+/// Roulette wheel selection over indices rather than elements. This is useful
+/// when the elements are stored elsewhere; `Roulette` is built on top of it.
+///
+/// A `RouletteIndex` may be empty, in which case `try_sample` returns `None`.
+pub struct RouletteIndex {
+    pub(crate) alias: Vec<usize>,
+    pub(crate) probability: Probability,
+    /// `None` if and only if the `RouletteIndex` is empty, since `Uniform`
+    /// can't represent an empty range.
+    pub(crate) range: Option<Uniform<usize>>,
+    /// The coin thresholds for `SamplingMethod::SingleWord`, as fractions of
+    /// 2^32, or `None` if that method isn't selected.
+    pub(crate) single_word: Option<Vec<u32>>,
+    /// The indices whose weight isn't zero, but is so much smaller than the
+    /// sum that their probability rounded to zero, with their weights, in
+    /// ascending order of index. This is almost always empty.
+    pub(crate) underflow: Vec<(usize, f64)>,
+}
+
+/// How a `RouletteIndex` or `Roulette` turns random numbers into samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplingMethod {
+    /// Draws the column and the coin flip separately. This is the default,
+    /// and has no bias beyond that of the alias table itself.
+    TwoDraws,
+    /// Draws a single `u64` per sample: the high half of its product with the
+    /// number of columns chooses the column, and the low half is compared to
+    /// the column's coin threshold, rounded to a multiple of 2^-32.
+    ///
+    /// This is faster, but slightly biased: the probability of each index
+    /// differs from its effective probability under `TwoDraws` by at most
+    /// 2^-32 + n * 2^-63, where n is the number of indices.
+    SingleWord,
+}
+
+/// The chance of each column returning its own index rather than its alias.
+pub(crate) enum Probability {
+    /// Probabilities between 0 and 1.
+    Float(Vec<f64>),
+    /// Numerators over a common denominator, for sampling without any rounding.
+    Exact {
+        numerators: Vec<u64>,
+        denominator: u64,
+        coin: Uniform<u64>,
+    },
+}
+
+impl RouletteIndex {
+    /// Creates a `RouletteIndex` that returns each index with a probability
+    /// proportional to its weight. The weights don't have to sum to 1;
+    /// they will be normalized automatically.
+    ///
+    /// Panics if the weights are invalid; see `RouletteIndex::try_new`.
+    pub fn new(weights: &[f64]) -> RouletteIndex {
+        match RouletteIndex::try_new(weights) {
+            Ok(index) => index,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `RouletteIndex` like `RouletteIndex::new`, but returns an
+    /// error instead of panicking if the weights are all zero, or if any are
+    /// negative, NaN or infinite.
+    ///
+    /// An empty slice of weights gives an empty `RouletteIndex`.
+    pub fn try_new(weights: &[f64]) -> Result<RouletteIndex, RouletteError> {
+        if weights.is_empty() {
+            return Ok(RouletteIndex::empty());
+        }
+        let sum = error::check_weights(weights.iter().cloned())?;
+
+        let len = weights.len();
+        let range = Some(Uniform::from(0..len));
+
+        // Dividing rather than multiplying by `1.0 / sum` keeps this finite
+        // when the sum is subnormal.
+        let mut prob: Vec<_> = weights.iter().map(|x| x / sum).collect();
+
+        let average = 1.0 / len as f64;
+        let mut small = Vec::new();
+        let mut large = Vec::new();
+        for (i, prob) in prob.iter().enumerate().take(len) {
+            if *prob >= average {
+                large.push(i);
+            } else {
+                small.push(i);
+            }
+        }
+
+        let mut alias = vec![0; len];
+        let mut probability = vec![0.0; len];
+
+        while !small.is_empty() && !large.is_empty() {
+            let less = small.pop().unwrap();
+            let more = large.pop().unwrap();
+            probability[less] = prob[less] * len as f64;
+            alias[less] = more;
+            prob[more] = (prob[more] + prob[less]) - average;
+            if prob[more] >= average {
+                large.push(more);
+            } else {
+                small.push(more);
+            }
+        }
+
+        while !small.is_empty() {
+            probability[small.pop().unwrap()] = 1.0;
+        }
+        while !large.is_empty() {
+            probability[large.pop().unwrap()] = 1.0;
+        }
+
+        Ok(RouletteIndex {
+            alias,
+            probability: Probability::Float(probability),
+            range,
+            single_word: None,
+            underflow: underflow(weights, sum),
+        })
+    }
+
+    /// Creates a `RouletteIndex` from integer weights. Unlike
+    /// `RouletteIndex::new`, the alias table is built using only integer
+    /// arithmetic, so each index is returned with a probability of exactly its
+    /// weight divided by the sum of the weights (assuming the `Rng` is
+    /// uniform).
+    ///
+    /// Panics if the weights are invalid; see
+    /// `RouletteIndex::try_from_integer_weights`.
+    pub fn from_integer_weights(weights: &[u64]) -> RouletteIndex {
+        match RouletteIndex::try_from_integer_weights(weights) {
+            Ok(index) => index,
+            Err(err) => panic!("Invalid weights in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `RouletteIndex` like `RouletteIndex::from_integer_weights`,
+    /// but returns an error instead of panicking if the weights are all zero
+    /// or if their sum doesn't fit in a `u64`.
+    ///
+    /// An empty slice of weights gives an empty `RouletteIndex`.
+    pub fn try_from_integer_weights(weights: &[u64]) -> Result<RouletteIndex, RouletteError> {
+        if weights.is_empty() {
+            return Ok(RouletteIndex::empty());
+        }
+        let sum = weights
+            .iter()
+            .try_fold(0u64, |sum, &weight| sum.checked_add(weight))
+            .ok_or(RouletteError::SumOverflow)?;
+        if sum == 0 {
+            return Err(RouletteError::ZeroSum);
+        }
+
+        // Each column holds `sum` units, and each index has `weight * len`
+        // units to distribute, so the totals match exactly. No column's share
+        // ever exceeds `sum`, but the remaining units of a large index can,
+        // so those are tracked as `u128`.
+        let len = weights.len();
+        let mut units: Vec<u128> = weights
+            .iter()
+            .map(|&weight| u128::from(weight) * len as u128)
+            .collect();
+
+        let mut small = Vec::new();
+        let mut large = Vec::new();
+        for (i, &units) in units.iter().enumerate() {
+            if units >= u128::from(sum) {
+                large.push(i);
+            } else {
+                small.push(i);
+            }
+        }
+
+        let mut alias: Vec<usize> = (0..len).collect();
+        let mut numerators = vec![sum; len];
+
+        while !small.is_empty() && !large.is_empty() {
+            let less = small.pop().unwrap();
+            let more = large.pop().unwrap();
+            numerators[less] = units[less] as u64;
+            alias[less] = more;
+            units[more] = (units[more] + units[less]) - u128::from(sum);
+            if units[more] >= u128::from(sum) {
+                large.push(more);
+            } else {
+                small.push(more);
+            }
+        }
+        // Since the arithmetic is exact, any remaining indices have exactly
+        // `sum` units, so they keep their whole column.
+
+        Ok(RouletteIndex {
+            alias,
+            probability: Probability::Exact {
+                numerators,
+                denominator: sum,
+                coin: Uniform::from(0..sum),
+            },
+            range: Some(Uniform::from(0..len)),
+            single_word: None,
+            underflow: Vec::new(),
+        })
+    }
+
+    /// Creates a `RouletteIndex` with no indices.
+    pub fn empty() -> RouletteIndex {
+        RouletteIndex {
+            alias: Vec::new(),
+            probability: Probability::Float(Vec::new()),
+            range: None,
+            single_word: None,
+            underflow: Vec::new(),
+        }
+    }
+
+    /// Returns the number of indices, including those with zero probability.
+    pub fn len(&self) -> usize {
+        self.alias.len()
+    }
+
+    /// Returns true if the `RouletteIndex` has no indices.
+    pub fn is_empty(&self) -> bool {
+        self.alias.is_empty()
+    }
+
+    /// Returns a random index; each index's chance of being returned is
+    /// proportional to its weight.
+    ///
+    /// Panics if the `RouletteIndex` is empty.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.try_sample(rng)
+            .expect("Can't sample from an empty Roulette")
+    }
+
+    /// Returns a random index like `RouletteIndex::sample`, or `None` if the
+    /// `RouletteIndex` is empty.
+    pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+        let range = self.range.as_ref()?;
+        if let Some(ref thresholds) = self.single_word {
+            return Some(self.single_word_index(thresholds, rng.gen()));
+        }
+        let column = range.sample(rng);
+        let coin = match self.probability {
+            Probability::Float(ref probability) => rng.gen::<f64>() < probability[column],
+            Probability::Exact {
+                ref numerators,
+                ref coin,
+                ..
+            } => coin.sample(rng) < numerators[column],
+        };
+        Some(if coin { column } else { self.alias[column] })
+    }
+
+    /// Returns `k` distinct random indices, in the order they were drawn.
+    ///
+    /// This is equivalent to sampling an index, removing it, and repeating
+    /// `k` times, so each draw picks among the remaining indices with a chance
+    /// proportional to their weights. This takes O(n + k log n) time.
+    ///
+    /// Returns an error if fewer than `k` indices have a non-zero weight.
+    ///
+    /// A weight that's tiny compared to the sum can round to a probability of
+    /// zero, so that `sample` never returns it. Such an index is still drawn
+    /// here once every index with a non-zero probability has been, and among
+    /// themselves they're drawn in proportion to their weights.
+    pub fn sample_distinct<R: Rng + ?Sized>(
+        &self,
+        k: usize,
+        rng: &mut R,
+    ) -> Result<Vec<usize>, RouletteError> {
+        let weights = self.effective_probabilities();
+        let positive = weights.iter().filter(|&&weight| weight > 0.0).count();
+        let underflow: Vec<(usize, f64)> = self
+            .underflow
+            .iter()
+            .cloned()
+            .filter(|&(index, _)| weights[index] == 0.0)
+            .collect();
+        let available = positive + underflow.len();
+        if k > available {
+            return Err(RouletteError::NotEnoughItems {
+                requested: k,
+                available,
+            });
+        }
+
+        let mut chosen = Vec::with_capacity(k);
+        let weights: Vec<(usize, f64)> = weights.into_iter().enumerate().collect();
+        draw_distinct(&weights, k.min(positive), &mut chosen, rng);
+        draw_distinct(&underflow, k - chosen.len(), &mut chosen, rng);
+        Ok(chosen)
+    }
+
+    /// Returns the probability of each index being returned by `sample`,
+    /// reconstructed from the alias table (assuming the `Rng` is uniform).
+    ///
+    /// These can differ slightly from the normalized weights, due to rounding
+    /// while building the table; see `RouletteIndex::max_error_vs_input`. For
+    /// a table built by `RouletteIndex::from_integer_weights` they're exact up
+    /// to the final division.
+    pub fn effective_probabilities(&self) -> Vec<f64> {
+        let len = self.len() as f64;
+        match self.probability {
+            Probability::Float(ref probability) => {
+                let mut result = probability.clone();
+                for (column, &probability) in probability.iter().enumerate() {
+                    if probability < 1.0 {
+                        result[self.alias[column]] += 1.0 - probability;
+                    }
+                }
+                for probability in &mut result {
+                    *probability /= len;
+                }
+                result
+            }
+            Probability::Exact {
+                ref numerators,
+                denominator,
+                ..
+            } => {
+                let mut units: Vec<u128> = numerators.iter().map(|&n| u128::from(n)).collect();
+                for (column, &numerator) in numerators.iter().enumerate() {
+                    units[self.alias[column]] += u128::from(denominator - numerator);
+                }
+                let total = len * denominator as f64;
+                units
+                    .into_iter()
+                    .map(|units| units as f64 / total)
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the probability of `index` being returned by `sample`, like
+    /// `RouletteIndex::effective_probabilities`. This takes O(n) time, since
+    /// any column may use `index` as its alias.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn probability_of(&self, index: usize) -> f64 {
+        assert!(index < self.len(), "Roulette index out of bounds");
+        let len = self.len() as f64;
+        let aliased_to = |column: &usize| self.alias[*column] == index;
+        match self.probability {
+            Probability::Float(ref probability) => {
+                let aliased: f64 = (0..self.len())
+                    .filter(aliased_to)
+                    .map(|column| 1.0 - probability[column])
+                    .sum();
+                (probability[index] + aliased) / len
+            }
+            Probability::Exact {
+                ref numerators,
+                denominator,
+                ..
+            } => {
+                let aliased: u128 = (0..self.len())
+                    .filter(aliased_to)
+                    .map(|column| u128::from(denominator - numerators[column]))
+                    .sum();
+                (u128::from(numerators[index]) + aliased) as f64 / (len * denominator as f64)
+            }
+        }
+    }
+
+    /// Returns the largest absolute difference between the effective
+    /// probability of an index and its normalized weight in `weights`, which
+    /// should be the weights this `RouletteIndex` was built from. The table
+    /// doesn't keep its input, since that would double its size.
+    ///
+    /// Panics if `weights` has the wrong length or if its sum isn't positive.
+    pub fn max_error_vs_input(&self, weights: &[f64]) -> f64 {
+        assert_eq!(weights.len(), self.len(), "Wrong number of weights");
+        let sum: f64 = weights.iter().sum();
+        assert!(sum > 0.0, "Weights must have a positive sum");
+        self.effective_probabilities()
+            .iter()
+            .zip(weights)
+            .map(|(probability, weight)| (probability - weight / sum).abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// Selects how samples are drawn; see `SamplingMethod`. This isn't saved
+    /// when the `RouletteIndex` is serialized.
+    pub fn with_sampling_method(mut self, method: SamplingMethod) -> RouletteIndex {
+        self.single_word = match method {
+            SamplingMethod::TwoDraws => None,
+            SamplingMethod::SingleWord => Some(self.thresholds()),
+        };
+        self
+    }
+
+    /// Returns how samples are drawn.
+    pub fn sampling_method(&self) -> SamplingMethod {
+        match self.single_word {
+            Some(_) => SamplingMethod::SingleWord,
+            None => SamplingMethod::TwoDraws,
+        }
+    }
+
+    /// Computes the coin thresholds for `SamplingMethod::SingleWord`. A
+    /// threshold can't represent a probability of 1, so columns that always
+    /// return their own index are made their own alias instead.
+    fn thresholds(&mut self) -> Vec<u32> {
+        let len = self.len();
+        let mut thresholds = Vec::with_capacity(len);
+        for column in 0..len {
+            thresholds.push(match self.threshold(column) {
+                Some(threshold) => threshold,
+                None => {
+                    self.alias[column] = column;
+                    u32::MAX
+                }
+            });
+        }
+        thresholds
+    }
+
+    /// Returns the coin threshold of `column` for `SamplingMethod::SingleWord`,
+    /// as a fraction of 2^32, or `None` if the column always returns its own
+    /// index.
+    pub(crate) fn threshold(&self, column: usize) -> Option<u32> {
+        let (certain, threshold) = match self.probability {
+            Probability::Float(ref probability) => {
+                let p = probability[column];
+                (p >= 1.0, u64::from(fixed_point(p)))
+            }
+            Probability::Exact {
+                ref numerators,
+                denominator,
+                ..
+            } => {
+                let numerator = numerators[column];
+                let threshold = ((u128::from(numerator) << 32) + u128::from(denominator / 2))
+                    / u128::from(denominator);
+                (numerator == denominator, threshold as u64)
+            }
+        };
+        if certain {
+            None
+        } else {
+            Some(threshold.min(u64::from(u32::MAX)) as u32)
+        }
+    }
+
+    /// Returns the index that `word` chooses under
+    /// `SamplingMethod::SingleWord`.
+    pub(crate) fn single_word_index(&self, thresholds: &[u32], word: u64) -> usize {
+        let product = u128::from(word) * self.len() as u128;
+        let column = (product >> 64) as usize;
+        let coin = (product as u64 >> 32) as u32;
+        if coin < thresholds[column] {
+            column
+        } else {
+            self.alias[column]
+        }
+    }
+}
+
+/// Rounds a probability between 0 and 1 to a fraction of 2^32, saturating at
+/// `u32::MAX`.
+pub(crate) fn fixed_point(p: f64) -> u32 {
+    const SCALE: f64 = (1u64 << 32) as f64;
+    ((p * SCALE + 0.5) as u64).min(u64::from(u32::MAX)) as u32
+}
+
+/// Returns the indices of the non-zero weights whose probability rounds to
+/// zero when they're divided by `sum`, with their weights.
+pub(crate) fn underflow(weights: &[f64], sum: f64) -> Vec<(usize, f64)> {
+    weights
+        .iter()
+        .cloned()
+        .enumerate()
+        .filter(|&(_, weight)| weight > 0.0 && weight / sum == 0.0)
+        .collect()
+}
+
+/// Draws `k` distinct indices from `weights` by successive removal, adding
+/// them to `chosen`.
+fn draw_distinct<R: Rng + ?Sized>(
+    weights: &[(usize, f64)],
+    k: usize,
+    chosen: &mut Vec<usize>,
+    rng: &mut R,
+) {
+    if k == 0 {
+        return;
+    }
+    let mut remaining = DynamicRoulette::new(
+        weights
+            .iter()
+            .enumerate()
+            .map(|(position, &(_, weight))| (position, weight))
+            .collect(),
+    );
+    for _ in 0..k {
+        let position = *remaining.sample(rng);
+        remaining
+            .set_weight(position, 0.0)
+            .expect("Zero is always a valid weight");
+        chosen.push(weights[position].0);
+    }
+}
+
+impl Default for RouletteIndex {
+    fn default() -> RouletteIndex {
+        RouletteIndex::empty()
+    }
+}
+
+/// The coin probabilities of an alias table that was stored, before they're
+/// validated by `RouletteIndex::from_parts`.
+#[cfg(any(feature = "std", feature = "serde"))]
+pub(crate) enum StoredProbability {
+    Float(Vec<f64>),
+    Exact {
+        numerators: Vec<u64>,
+        denominator: u64,
+    },
+}
+
+#[cfg(any(feature = "std", feature = "serde"))]
+impl RouletteIndex {
+    /// Builds a `RouletteIndex` from an alias table that was stored, such as
+    /// by `serde` or in a file, validating it so that a corrupted or tampered
+    /// table can't make `sample` index out of bounds.
+    pub(crate) fn from_parts(
+        alias: Vec<usize>,
+        probability: StoredProbability,
+        underflow: Vec<(usize, f64)>,
+    ) -> Result<RouletteIndex, &'static str> {
+        let len = alias.len();
+        check_alias(len, alias.iter().cloned())?;
+        check_underflow(len, underflow.iter().cloned())?;
+        let probability = match probability {
+            StoredProbability::Float(probability) => {
+                if probability.len() != len {
+                    return Err("Roulette probability table has the wrong length");
+                }
+                check_probabilities(probability.iter().cloned())?;
+                Probability::Float(probability)
+            }
+            StoredProbability::Exact {
+                numerators,
+                denominator,
+            } => {
+                if numerators.len() != len {
+                    return Err("Roulette probability table has the wrong length");
+                }
+                check_numerators(numerators.iter().cloned(), denominator)?;
+                Probability::Exact {
+                    numerators,
+                    denominator,
+                    coin: Uniform::from(0..denominator),
+                }
+            }
+        };
+        Ok(RouletteIndex {
+            alias,
+            probability,
+            range: if len == 0 {
+                None
+            } else {
+                Some(Uniform::from(0..len))
+            },
+            single_word: None,
+            underflow,
+        })
+    }
+}
+
+/// Checks that every alias of a stored table is one of its `len` indices.
+#[cfg(any(feature = "std", feature = "serde"))]
+pub(crate) fn check_alias<I>(len: usize, alias: I) -> Result<(), &'static str>
+where
+    I: IntoIterator<Item = usize>,
+{
+    if alias.into_iter().any(|alias| alias >= len) {
+        return Err("Roulette alias table is out of bounds");
+    }
+    Ok(())
+}
+
+/// Checks that the underflowed weights of a stored table are positive and
+/// finite, and that their indices are in bounds and ascending.
+#[cfg(any(feature = "std", feature = "serde"))]
+pub(crate) fn check_underflow<I>(len: usize, underflow: I) -> Result<(), &'static str>
+where
+    I: IntoIterator<Item = (usize, f64)>,
+{
+    let mut next = 0;
+    for (index, weight) in underflow {
+        if index < next || index >= len || !(weight > 0.0 && weight.is_finite()) {
+            return Err("Roulette underflowed weights are invalid");
+        }
+        next = index + 1;
+    }
+    Ok(())
+}
+
+/// Checks that the coin probabilities of a stored table are between 0 and 1.
+#[cfg(any(feature = "std", feature = "serde"))]
+pub(crate) fn check_probabilities<I>(probability: I) -> Result<(), &'static str>
+where
+    I: IntoIterator<Item = f64>,
+{
+    // This also rejects NaN.
+    if !probability.into_iter().all(|p| (0.0..=1.0).contains(&p)) {
+        return Err("Roulette probabilities must be between 0 and 1");
+    }
+    Ok(())
+}
+
+/// Checks the coin numerators of a stored table against their denominator.
+#[cfg(any(feature = "std", feature = "serde"))]
+pub(crate) fn check_numerators<I>(numerators: I, denominator: u64) -> Result<(), &'static str>
+where
+    I: IntoIterator<Item = u64>,
+{
+    if denominator == 0 {
+        return Err("Roulette denominator must not be zero");
+    }
+    if numerators
+        .into_iter()
+        .any(|numerator| numerator > denominator)
+    {
+        return Err("Roulette numerators must be between 0 and the denominator");
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use stats::tests::{assert_fits, WEIGHTS};
+
+    #[test]
+    fn indices() {
+        let index = RouletteIndex::new(&[0.0, 0.0, 2.0]);
+        assert_eq!(index.len(), 3);
+        for _ in 0..10 {
+            assert_eq!(index.sample(&mut rand::thread_rng()), 2);
+        }
+        assert_eq!(
+            RouletteIndex::try_new(&[1.0, f64::INFINITY]).err(),
+            Some(RouletteError::Infinite { index: 1 })
+        );
+        assert_eq!(
+            RouletteIndex::empty().try_sample(&mut rand::thread_rng()),
+            None
+        );
+    }
+
+    #[test]
+    fn effective_probabilities() {
+        let weights = [0.5, 0.0, 3.0, 1.5, 0.0, 0.25];
+        let index = RouletteIndex::new(&weights);
+        assert!(index.max_error_vs_input(&weights) < 1e-15);
+        let probabilities = index.effective_probabilities();
+        for (i, &probability) in probabilities.iter().enumerate() {
+            assert_eq!(probability, index.probability_of(i));
+        }
+        assert_eq!(probabilities[1], 0.0);
+        assert_eq!(probabilities[4], 0.0);
+
+        let index = RouletteIndex::from_integer_weights(&[1, 2, 0, 7]);
+        assert_eq!(index.effective_probabilities(), vec![0.1, 0.2, 0.0, 0.7]);
+        assert_eq!(index.probability_of(3), 0.7);
+    }
+
+    #[test]
+    fn random_tables() {
+        let mut rng = rand::thread_rng();
+        for len in 1..200 {
+            let weights: Vec<f64> = (0..len)
+                .map(|_| {
+                    if rng.gen_bool(0.3) {
+                        0.0
+                    } else {
+                        rng.gen_range(0.0, 1e6)
+                    }
+                })
+                .collect();
+            if weights.iter().all(|&weight| weight == 0.0) {
+                continue;
+            }
+            let index = RouletteIndex::new(&weights);
+            assert!(index.max_error_vs_input(&weights) < 1e-12);
+            for (weight, probability) in weights.iter().zip(index.effective_probabilities()) {
+                if *weight == 0.0 {
+                    assert_eq!(probability, 0.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_word() {
+        let index = RouletteIndex::new(&WEIGHTS).with_sampling_method(SamplingMethod::SingleWord);
+        assert_eq!(index.sampling_method(), SamplingMethod::SingleWord);
+        assert_fits(16, |rng| index.sample(rng));
+
+        let index = RouletteIndex::from_integer_weights(&[1, 0, 3])
+            .with_sampling_method(SamplingMethod::SingleWord);
+        let counts = ::stats::sample_counts(index.len(), 200_000, 16, |rng| index.sample(rng));
+        assert_eq!(counts[1], 0);
+        assert!(::stats::chi_squared(&counts, &[1.0, 0.0, 3.0]).p_value > 0.001);
+
+        // Both columns are certain, so the extreme words stay in their column.
+        let index =
+            RouletteIndex::new(&[1.0, 1.0]).with_sampling_method(SamplingMethod::SingleWord);
+        let thresholds = index.single_word.as_ref().unwrap();
+        assert_eq!(index.single_word_index(thresholds, 0), 0);
+        assert_eq!(index.single_word_index(thresholds, u64::MAX >> 1), 0);
+        assert_eq!(index.single_word_index(thresholds, u64::MAX), 1);
+        let index = index.with_sampling_method(SamplingMethod::TwoDraws);
+        assert_eq!(index.sampling_method(), SamplingMethod::TwoDraws);
+    }
+}
@@ -16,10 +16,12 @@
 //! extern crate rand;
 //! extern crate roulette;
 //!
+//! use rand::rngs::StdRng;
+//! use rand::SeedableRng;
 //! use roulette::Roulette;
 //!
 //! fn main() {
-//!     let mut rng = rand::thread_rng();
+//!     let mut rng = StdRng::seed_from_u64(0);
 //!     let roulette = Roulette::new(vec![('a', 1.0), ('b', 1.0), ('c', 0.5), ('d', 0.0)]);
 //!     for _ in 0..10 {
 //!         let rand = roulette.sample(&mut rng);
@@ -28,18 +30,75 @@
 //! }
 //! ```
 
+#![no_std]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
 extern crate rand;
+#[cfg(feature = "rand_0_10")]
+extern crate rand_0_10;
+#[cfg(feature = "rand_0_8")]
+extern crate rand_0_8;
+#[cfg(feature = "rand_0_9")]
+extern crate rand_0_9;
+#[cfg(feature = "rayon")]
+extern crate rand_chacha;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod batch;
+mod cdf;
+mod compact;
+mod distribution;
+mod dynamic;
+mod error;
+mod fenwick;
+#[cfg(feature = "std")]
+pub mod file;
+mod fldr;
+mod index;
+#[cfg(feature = "std")]
+mod logits;
+#[cfg(feature = "rayon")]
+mod parallel;
+pub mod resample;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod tokens;
+mod uniform;
 
-use rand::distributions::{Distribution, Uniform};
+pub use batch::SampleIter;
+pub use cdf::CdfRoulette;
+pub use compact::CompactRoulette;
+pub use distribution::Elements;
+pub use dynamic::DynamicRoulette;
+pub use error::RouletteError;
+pub use fenwick::FenwickRoulette;
+pub use fldr::{FldrRoulette, RandomBits};
+pub use index::{RouletteIndex, SamplingMethod};
+
+use alloc::vec::Vec;
+use core::iter::FromIterator;
 use rand::Rng;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
 /// An efficient implementation of roulette wheel selection. This can be
 /// used to simulate a loaded die.
+///
+/// A `Roulette` may be empty, in which case `try_sample` returns `None`.
 pub struct Roulette<T> {
-    probabilities: Vec<T>,
-    alias: Vec<usize>,
-    probability: Vec<f64>,
-    range: Uniform<usize>,
+    items: Vec<T>,
+    index: RouletteIndex,
 }
 
 impl<T> Roulette<T> {
@@ -47,81 +106,221 @@ impl<T> Roulette<T> {
     /// Note that the probabilities don't have to sum to 1;
     /// they will be normalized automatically.
     ///
-    /// Panics if the probabilities are all zero or if any are negative.
+    /// Panics if the probabilities are invalid; see `Roulette::try_new`.
     pub fn new(probabilities: Vec<(T, f64)>) -> Roulette<T> {
-        let len = probabilities.len();
-        let range = Uniform::from(0..len);
+        match Roulette::try_new(probabilities) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
 
-        let sum: f64 = probabilities.iter().map(|x| x.1).sum();
+    /// Creates a `Roulette` like `Roulette::new`, but returns an error instead
+    /// of panicking if the probabilities are all zero, or if any are negative,
+    /// NaN or infinite.
+    ///
+    /// An empty list of probabilities gives an empty `Roulette`.
+    pub fn try_new(probabilities: Vec<(T, f64)>) -> Result<Roulette<T>, RouletteError> {
+        let (items, weights): (Vec<T>, Vec<f64>) = probabilities.into_iter().unzip();
+        let index = RouletteIndex::try_new(&weights)?;
+        Ok(Roulette { items, index })
+    }
 
-        for prob in &probabilities {
-            if prob.1 < 0.0 {
-                panic!("Invalid probability in Roulette: must not be negative");
-            }
-        }
-        assert!(sum != 0.0, "Probabilities in Roulette must not all be zero");
-
-        let inv_sum = 1.0 / sum;
-        let mut prob: Vec<_> = probabilities.iter().map(|x| x.1 * inv_sum).collect();
-
-        let average = 1.0 / len as f64;
-        let mut small = Vec::new();
-        let mut large = Vec::new();
-        for (i, prob) in prob.iter().enumerate().take(len) {
-            if *prob >= average {
-                large.push(i);
-            } else {
-                small.push(i);
-            }
-        }
+    /// Creates a `Roulette` from a map from elements to their probabilities.
+    /// The elements are stored in the map's iteration order.
+    ///
+    /// Panics if the probabilities are invalid; see `Roulette::try_new`.
+    #[cfg(feature = "std")]
+    pub fn from_map<S>(map: HashMap<T, f64, S>) -> Roulette<T> {
+        Roulette::new(map.into_iter().collect())
+    }
 
-        let mut alias = vec![0; len];
-        let mut probability = vec![0.0; len];
-
-        while !small.is_empty() && !large.is_empty() {
-            let less = small.pop().unwrap();
-            let more = large.pop().unwrap();
-            probability[less] = prob[less] * len as f64;
-            alias[less] = more;
-            prob[more] = (prob[more] + prob[less]) - average;
-            if prob[more] >= average {
-                large.push(more);
-            } else {
-                small.push(more);
-            }
-        }
+    /// Creates a `Roulette` like `Roulette::from_map`, but returns an error
+    /// instead of panicking if the probabilities are invalid.
+    #[cfg(feature = "std")]
+    pub fn try_from_map<S>(map: HashMap<T, f64, S>) -> Result<Roulette<T>, RouletteError> {
+        Roulette::try_new(map.into_iter().collect())
+    }
 
-        while !small.is_empty() {
-            probability[small.pop().unwrap()] = 1.0;
+    /// Creates a `Roulette` from a set of elements and a function that
+    /// returns the probability of each element.
+    ///
+    /// Panics if the probabilities are invalid; see `Roulette::try_new`.
+    pub fn from_items_by<F>(items: Vec<T>, probability: F) -> Roulette<T>
+    where
+        F: FnMut(&T) -> f64,
+    {
+        match Roulette::try_from_items_by(items, probability) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
         }
-        while !large.is_empty() {
-            probability[large.pop().unwrap()] = 1.0;
+    }
+
+    /// Creates a `Roulette` like `Roulette::from_items_by`, but returns an
+    /// error instead of panicking if the probabilities are invalid.
+    pub fn try_from_items_by<F>(items: Vec<T>, probability: F) -> Result<Roulette<T>, RouletteError>
+    where
+        F: FnMut(&T) -> f64,
+    {
+        let weights: Vec<f64> = items.iter().map(probability).collect();
+        let index = RouletteIndex::try_new(&weights)?;
+        Ok(Roulette { items, index })
+    }
+
+    /// Creates a `Roulette` from integer weights. Unlike `Roulette::new`, the
+    /// alias table is built using only integer arithmetic, so each element is
+    /// returned with a probability of exactly its weight divided by the sum of
+    /// the weights (assuming the `Rng` is uniform).
+    ///
+    /// Panics if the weights are invalid; see
+    /// `Roulette::try_from_integer_weights`.
+    pub fn from_integer_weights(weights: Vec<(T, u64)>) -> Roulette<T> {
+        match Roulette::try_from_integer_weights(weights) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid weights in Roulette: {}", err),
         }
+    }
 
+    /// Creates a `Roulette` like `Roulette::from_integer_weights`, but returns
+    /// an error instead of panicking if the weights are all zero or if their
+    /// sum doesn't fit in a `u64`.
+    ///
+    /// An empty list of weights gives an empty `Roulette`.
+    pub fn try_from_integer_weights(weights: Vec<(T, u64)>) -> Result<Roulette<T>, RouletteError> {
+        let (items, weights): (Vec<T>, Vec<u64>) = weights.into_iter().unzip();
+        let index = RouletteIndex::try_from_integer_weights(&weights)?;
+        Ok(Roulette { items, index })
+    }
+
+    /// Creates a `Roulette` with no elements.
+    pub fn empty() -> Roulette<T> {
         Roulette {
-            probabilities: probabilities.into_iter().map(|x| x.0).collect(),
-            alias,
-            probability,
-            range,
+            items: Vec::new(),
+            index: RouletteIndex::empty(),
         }
     }
 
+    /// Returns the number of elements, including those with zero probability.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the `Roulette` has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the elements, in the order they were given.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns the `RouletteIndex` used to choose elements.
+    pub fn as_index(&self) -> &RouletteIndex {
+        &self.index
+    }
+
+    /// Selects how samples are drawn; see `SamplingMethod`.
+    pub fn with_sampling_method(mut self, method: SamplingMethod) -> Roulette<T> {
+        self.index = self.index.with_sampling_method(method);
+        self
+    }
+
+    /// Returns how samples are drawn.
+    pub fn sampling_method(&self) -> SamplingMethod {
+        self.index.sampling_method()
+    }
+
     /// Returns a random element; each element's chance of being returned
     /// is proportional to the probability specified in the parameter
     /// to `Roulette::new`.
-    pub fn sample<R: Rng>(&self, rng: &mut R) -> &T {
-        let column = self.range.sample(rng);
-        let coin = rng.gen::<f64>() < self.probability[column];
-        &self.probabilities[if coin { column } else { self.alias[column] }]
+    ///
+    /// Panics if the `Roulette` is empty.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &T {
+        &self.items[self.sample_index(rng)]
+    }
+
+    /// Returns a random element like `Roulette::sample`, or `None` if the
+    /// `Roulette` is empty.
+    pub fn try_sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        self.try_sample_index(rng).map(|index| &self.items[index])
+    }
+
+    /// Returns the index of a random element, chosen like in
+    /// `Roulette::sample`.
+    ///
+    /// Panics if the `Roulette` is empty.
+    pub fn sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.index.sample(rng)
+    }
+
+    /// Returns the index of a random element like `Roulette::sample_index`,
+    /// or `None` if the `Roulette` is empty.
+    pub fn try_sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<usize> {
+        self.index.try_sample(rng)
+    }
+
+    /// Returns the probability of each element being returned by `sample`;
+    /// see `RouletteIndex::effective_probabilities`.
+    pub fn effective_probabilities(&self) -> Vec<f64> {
+        self.index.effective_probabilities()
+    }
+
+    /// Returns the probability of the element at `index` being returned by
+    /// `sample`; see `RouletteIndex::probability_of`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn probability_of(&self, index: usize) -> f64 {
+        self.index.probability_of(index)
+    }
+
+    /// Returns the largest difference between the probability of an element
+    /// being returned by `sample` and its normalized probability in
+    /// `probabilities`, which should be the probabilities this `Roulette` was
+    /// built from; see `RouletteIndex::max_error_vs_input`.
+    pub fn max_error_vs_input(&self, probabilities: &[f64]) -> f64 {
+        self.index.max_error_vs_input(probabilities)
+    }
+
+    /// Returns `k` distinct random elements, in the order they were drawn.
+    ///
+    /// This is equivalent to sampling an element, removing it, and repeating
+    /// `k` times, so each draw picks among the remaining elements with a chance
+    /// proportional to their probabilities. This takes O(n + k log n) time.
+    ///
+    /// Returns an error if fewer than `k` elements have a non-zero weight; see
+    /// `RouletteIndex::sample_distinct`.
+    pub fn sample_distinct<R: Rng + ?Sized>(
+        &self,
+        k: usize,
+        rng: &mut R,
+    ) -> Result<Vec<&T>, RouletteError> {
+        let indices = self.index.sample_distinct(k, rng)?;
+        Ok(indices
+            .into_iter()
+            .map(|index| &self.items[index])
+            .collect())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    // TODO: is there a way to test the distribution returned from `sample` in a
-    // deterministic way?
+impl<T> Default for Roulette<T> {
+    fn default() -> Roulette<T> {
+        Roulette::empty()
+    }
+}
 
+/// Collects elements and their probabilities like `Roulette::new`.
+///
+/// Panics if the probabilities are invalid; see `Roulette::try_new`.
+impl<T> FromIterator<(T, f64)> for Roulette<T> {
+    fn from_iter<I: IntoIterator<Item = (T, f64)>>(iter: I) -> Roulette<T> {
+        Roulette::new(iter.into_iter().collect())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
     use super::*;
+    use stats::tests::{assert_fits, WEIGHTS};
 
     #[test]
     fn most_entries_zero() {
@@ -131,6 +330,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sample_index() {
+        let roulette = Roulette::new(vec![('a', 0.0), ('b', 0.0), ('c', 1.0)]);
+        assert_eq!(roulette.items(), &['a', 'b', 'c']);
+        for _ in 0..10 {
+            assert_eq!(2, roulette.sample_index(&mut rand::thread_rng()));
+        }
+    }
+
+    #[test]
+    fn constructors() {
+        let mut rng = rand::thread_rng();
+        let roulette: Roulette<char> = "abc"
+            .chars()
+            .map(|c| (c, if c == 'b' { 1.0 } else { 0.0 }))
+            .collect();
+        assert_eq!(&'b', roulette.sample(&mut rng));
+
+        let mut map = HashMap::new();
+        map.insert("a", 0.0);
+        map.insert("b", 2.0);
+        let roulette = Roulette::from_map(map);
+        assert_eq!(roulette.len(), 2);
+        assert_eq!(&"b", roulette.sample(&mut rng));
+
+        let roulette = Roulette::from_items_by(vec![1, 2, 3], |&x| (x % 2) as f64);
+        assert_ne!(&2, roulette.sample(&mut rng));
+        assert_eq!(
+            Roulette::try_from_items_by(vec![1, 2, 3], |&x| -x as f64).err(),
+            Some(RouletteError::Negative { index: 0 })
+        );
+    }
+
+    #[test]
+    fn distribution() {
+        let roulette = Roulette::new(WEIGHTS.iter().map(|&weight| ((), weight)).collect());
+        assert_fits(7, |rng| roulette.sample_index(rng));
+
+        let weights = [3, 0, 1, 5, 10, 2];
+        let roulette =
+            Roulette::from_integer_weights(weights.iter().map(|&weight| ((), weight)).collect());
+        let counts =
+            stats::sample_counts(weights.len(), 200_000, 7, |rng| roulette.sample_index(rng));
+        let weights: Vec<f64> = weights.iter().map(|&weight| weight as f64).collect();
+        assert!(stats::chi_squared(&counts, &weights).p_value > 0.001);
+    }
+
     #[test]
     #[should_panic]
     fn all_entries_zero() {
@@ -142,4 +388,102 @@ mod tests {
     fn negative_entry() {
         Roulette::new(vec![('a', 0.0), ('b', 1.0), ('c', 0.0), ('d', -0.5)]);
     }
+
+    #[test]
+    fn subnormal_sum() {
+        let roulette = Roulette::new(vec![('a', 0.0), ('b', 5e-324)]);
+        for _ in 0..10 {
+            assert_eq!(&'b', roulette.sample(&mut rand::thread_rng()));
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let roulette: Roulette<char> = Roulette::new(vec![]);
+        assert!(roulette.is_empty());
+        assert_eq!(roulette.len(), 0);
+        assert_eq!(roulette.try_sample(&mut rand::thread_rng()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty Roulette")]
+    fn sample_empty() {
+        Roulette::<char>::empty().sample(&mut rand::thread_rng());
+    }
+
+    #[test]
+    fn sample_distinct() {
+        let mut rng = rand::thread_rng();
+        let roulette = Roulette::new(vec![('a', 1.0), ('b', 0.0), ('c', 1e-9), ('d', 5.0)]);
+        for _ in 0..10 {
+            let mut chosen = roulette.sample_distinct(3, &mut rng).unwrap();
+            chosen.sort();
+            assert_eq!(chosen, vec![&'a', &'c', &'d']);
+        }
+        assert_eq!(roulette.sample_distinct(0, &mut rng), Ok(vec![]));
+        assert_eq!(
+            roulette.sample_distinct(4, &mut rng),
+            Err(RouletteError::NotEnoughItems {
+                requested: 4,
+                available: 3
+            })
+        );
+
+        // 'b' rounds to a probability of zero, but it's still drawn once 'a'
+        // has been.
+        let roulette = Roulette::new(vec![('a', 1e300), ('b', 1e-300), ('c', 0.0)]);
+        assert_eq!(roulette.effective_probabilities()[1], 0.0);
+        assert_eq!(roulette.sample_distinct(2, &mut rng), Ok(vec![&'a', &'b']));
+        assert_eq!(
+            roulette.sample_distinct(3, &mut rng),
+            Err(RouletteError::NotEnoughItems {
+                requested: 3,
+                available: 2
+            })
+        );
+    }
+
+    #[test]
+    fn integer_weights() {
+        let roulette = Roulette::from_integer_weights(vec![('a', 0), ('b', 3), ('c', 0)]);
+        for _ in 0..10 {
+            assert_eq!(&'b', roulette.sample(&mut rand::thread_rng()));
+        }
+
+        let roulette = Roulette::from_integer_weights(vec![(0, 1), (1, 2), (2, 0), (3, 7)]);
+        assert_eq!(roulette.effective_probabilities(), vec![0.1, 0.2, 0.0, 0.7]);
+
+        assert_eq!(
+            Roulette::try_from_integer_weights(vec![('a', 0), ('b', 0)]).err(),
+            Some(RouletteError::ZeroSum)
+        );
+        assert_eq!(
+            Roulette::try_from_integer_weights(vec![('a', u64::MAX), ('b', 1)]).err(),
+            Some(RouletteError::SumOverflow)
+        );
+    }
+
+    #[test]
+    fn try_new_errors() {
+        assert_eq!(
+            Roulette::try_new(vec![('a', 0.0), ('b', 0.0)]).err(),
+            Some(RouletteError::ZeroSum)
+        );
+        assert_eq!(
+            Roulette::try_new(vec![('a', 1.0), ('b', -0.5)]).err(),
+            Some(RouletteError::Negative { index: 1 })
+        );
+        assert_eq!(
+            Roulette::try_new(vec![('a', f64::NAN), ('b', 1.0)]).err(),
+            Some(RouletteError::NaN { index: 0 })
+        );
+        assert_eq!(
+            Roulette::try_new(vec![('a', 1.0), ('b', f64::NEG_INFINITY)]).err(),
+            Some(RouletteError::Infinite { index: 1 })
+        );
+        assert_eq!(
+            Roulette::try_new(vec![('a', f64::MAX), ('b', f64::MAX)]).err(),
+            Some(RouletteError::SumOverflow)
+        );
+    }
 }
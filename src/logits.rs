@@ -0,0 +1,183 @@
+//! Constructors that take log-weights or logits, normalizing them with the
+//! log-sum-exp trick so that weights too small to represent as an `f64`
+//! don't all underflow to zero.
+
+use std::vec::Vec;
+
+use error::RouletteError;
+use {Roulette, RouletteIndex};
+
+impl RouletteIndex {
+    /// Creates a `RouletteIndex` from the natural logs of the weights, which
+    /// don't have to be normalized. A log-weight of negative infinity is a
+    /// weight of zero.
+    ///
+    /// Panics if the log-weights are invalid; see
+    /// `RouletteIndex::try_from_log_weights`.
+    pub fn from_log_weights(log_weights: &[f64]) -> RouletteIndex {
+        match RouletteIndex::try_from_log_weights(log_weights) {
+            Ok(index) => index,
+            Err(err) => panic!("Invalid log-weights in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `RouletteIndex` like `RouletteIndex::from_log_weights`, but
+    /// returns an error instead of panicking if any log-weight is NaN or
+    /// positive infinity, or if they're all negative infinity.
+    ///
+    /// An empty slice of log-weights gives an empty `RouletteIndex`.
+    pub fn try_from_log_weights(log_weights: &[f64]) -> Result<RouletteIndex, RouletteError> {
+        RouletteIndex::try_from_logits(log_weights, 1.0)
+    }
+
+    /// Creates a `RouletteIndex` from logits, where each index is returned
+    /// with a probability proportional to `exp(logit / temperature)`, as in a
+    /// softmax. Higher temperatures make the distribution more uniform.
+    ///
+    /// Panics if the logits or the temperature are invalid; see
+    /// `RouletteIndex::try_from_logits`.
+    pub fn from_logits(logits: &[f64], temperature: f64) -> RouletteIndex {
+        match RouletteIndex::try_from_logits(logits, temperature) {
+            Ok(index) => index,
+            Err(err) => panic!("Invalid logits in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `RouletteIndex` like `RouletteIndex::from_logits`, but
+    /// returns an error instead of panicking if any logit is NaN or positive
+    /// infinity, if they're all negative infinity, or if the temperature isn't
+    /// positive and finite.
+    ///
+    /// An empty slice of logits gives an empty `RouletteIndex`.
+    pub fn try_from_logits(
+        logits: &[f64],
+        temperature: f64,
+    ) -> Result<RouletteIndex, RouletteError> {
+        RouletteIndex::try_new(&softmax_weights(logits, temperature)?)
+    }
+}
+
+impl<T> Roulette<T> {
+    /// Creates a `Roulette` from the natural logs of the elements' weights;
+    /// see `RouletteIndex::from_log_weights`.
+    ///
+    /// Panics if the log-weights are invalid; see
+    /// `RouletteIndex::try_from_log_weights`.
+    pub fn from_log_weights(log_weights: Vec<(T, f64)>) -> Roulette<T> {
+        match Roulette::try_from_log_weights(log_weights) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid log-weights in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `Roulette` like `Roulette::from_log_weights`, but returns an
+    /// error instead of panicking if the log-weights are invalid.
+    pub fn try_from_log_weights(log_weights: Vec<(T, f64)>) -> Result<Roulette<T>, RouletteError> {
+        let (items, log_weights): (Vec<T>, Vec<f64>) = log_weights.into_iter().unzip();
+        let index = RouletteIndex::try_from_log_weights(&log_weights)?;
+        Ok(Roulette { items, index })
+    }
+
+    /// Creates a `Roulette` from logits for each element; see
+    /// `RouletteIndex::from_logits`.
+    ///
+    /// Panics if `items` and `logits` have different lengths, or if the logits
+    /// or the temperature are invalid; see `RouletteIndex::try_from_logits`.
+    pub fn from_logits(items: Vec<T>, logits: &[f64], temperature: f64) -> Roulette<T> {
+        match Roulette::try_from_logits(items, logits, temperature) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid logits in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `Roulette` like `Roulette::from_logits`, but returns an error
+    /// instead of panicking if the logits or the temperature are invalid.
+    ///
+    /// Panics if `items` and `logits` have different lengths.
+    pub fn try_from_logits(
+        items: Vec<T>,
+        logits: &[f64],
+        temperature: f64,
+    ) -> Result<Roulette<T>, RouletteError> {
+        assert_eq!(items.len(), logits.len(), "Wrong number of logits");
+        let index = RouletteIndex::try_from_logits(logits, temperature)?;
+        Ok(Roulette { items, index })
+    }
+}
+
+/// Converts logits to weights, scaled so that the largest weight is 1.
+pub(crate) fn softmax_weights(logits: &[f64], temperature: f64) -> Result<Vec<f64>, RouletteError> {
+    if !(temperature > 0.0 && temperature.is_finite()) {
+        return Err(RouletteError::InvalidTemperature);
+    }
+    let mut max = f64::NEG_INFINITY;
+    for (index, &logit) in logits.iter().enumerate() {
+        if logit.is_nan() {
+            return Err(RouletteError::NaN { index });
+        } else if logit == f64::INFINITY {
+            return Err(RouletteError::Infinite { index });
+        }
+        max = max.max(logit);
+    }
+    if max == f64::NEG_INFINITY && !logits.is_empty() {
+        return Err(RouletteError::ZeroSum);
+    }
+    // Subtracting the maximum before dividing by the temperature keeps the
+    // exponent at most 0 even when the temperature is tiny.
+    Ok(logits
+        .iter()
+        .map(|&logit| ((logit - max) / temperature).exp())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_weights() {
+        let index = RouletteIndex::from_log_weights(&[-1000.0, -1000.0 + 2f64.ln(), -1e308]);
+        let probabilities = index.effective_probabilities();
+        assert!((probabilities[0] - 1.0 / 3.0).abs() < 1e-12);
+        assert!((probabilities[1] - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(probabilities[2], 0.0);
+
+        let roulette = Roulette::from_log_weights(vec![('a', f64::NEG_INFINITY), ('b', -800.0)]);
+        for _ in 0..10 {
+            assert_eq!(&'b', roulette.sample(&mut rand::thread_rng()));
+        }
+
+        assert_eq!(
+            RouletteIndex::try_from_log_weights(&[f64::NEG_INFINITY; 2]).err(),
+            Some(RouletteError::ZeroSum)
+        );
+        assert_eq!(
+            RouletteIndex::try_from_log_weights(&[0.0, f64::INFINITY]).err(),
+            Some(RouletteError::Infinite { index: 1 })
+        );
+        assert_eq!(
+            RouletteIndex::try_from_log_weights(&[f64::NAN]).err(),
+            Some(RouletteError::NaN { index: 0 })
+        );
+        assert!(RouletteIndex::from_log_weights(&[]).is_empty());
+    }
+
+    #[test]
+    fn logits() {
+        let logits = [1.0, 2.0, 3.0];
+        let index = RouletteIndex::from_logits(&logits, 2.0);
+        let weights: Vec<f64> = logits.iter().map(|logit| (logit / 2.0f64).exp()).collect();
+        assert!(index.max_error_vs_input(&weights) < 1e-15);
+
+        // A tiny temperature makes sampling greedy without overflowing.
+        let roulette = Roulette::from_logits(vec!['a', 'b', 'c'], &[1e300, 2e300, 0.0], 1e-300);
+        assert_eq!(roulette.effective_probabilities(), vec![0.0, 1.0, 0.0]);
+
+        for &temperature in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                RouletteIndex::try_from_logits(&logits, temperature).err(),
+                Some(RouletteError::InvalidTemperature)
+            );
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Building alias tables in parallel with `rayon`.
+//!
+//! The serial construction pairs up underfull and overfull columns one at a
+//! time. Here, the columns are scaled so that the average is 1, and split into
+//! light ones (below 1) and heavy ones. Laying the lights' deficits end to end
+//! on one line and the heavies' excesses on another, each light takes its
+//! deficit from the heavy whose excess covers the start of it, and each heavy
+//! that runs out partway through a light fills the rest of its own column from
+//! the next heavy. With prefix sums of the deficits and excesses, every
+//! column's alias and probability can be found independently by binary
+//! search, so each step is parallel.
+//!
+//! Parallel sampling splits the samples into fixed blocks, each drawn with its
+//! own stream of a ChaCha generator, so the samples only depend on the seed.
+
+use std::vec::Vec;
+
+use rand::distributions::Uniform;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+
+use error::{self, RouletteError};
+use index::{self, Probability};
+use {Roulette, RouletteIndex};
+
+/// The number of weights each task sums at a time. Summing in fixed chunks
+/// keeps the results the same however many threads there are.
+const CHUNK: usize = 1 << 16;
+
+/// The number of samples drawn from each stream by parallel sampling.
+const BLOCK: usize = 1 << 16;
+
+impl RouletteIndex {
+    /// Creates a `RouletteIndex` like `RouletteIndex::new`, but builds it in
+    /// parallel. The alias table isn't the same as the one `new` builds, but
+    /// each index has the same probability of being returned, up to rounding.
+    ///
+    /// This makes a few more passes over the weights than `new` does, so it's
+    /// only faster for large tables with several threads available.
+    ///
+    /// Panics if the weights are invalid; see `RouletteIndex::try_new`.
+    pub fn par_new(weights: &[f64]) -> RouletteIndex {
+        match RouletteIndex::try_par_new(weights) {
+            Ok(index) => index,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `RouletteIndex` like `RouletteIndex::par_new`, but returns
+    /// the same error as `RouletteIndex::try_new` instead of panicking if the
+    /// weights are invalid.
+    pub fn try_par_new(weights: &[f64]) -> Result<RouletteIndex, RouletteError> {
+        if weights.is_empty() {
+            return Ok(RouletteIndex::empty());
+        }
+        let sum = check_weights(weights)?;
+
+        let len = weights.len();
+        let scaled = |weight: f64| weight / sum * len as f64;
+        let light: Vec<usize> = (0..len)
+            .into_par_iter()
+            .filter(|&i| scaled(weights[i]) < 1.0)
+            .collect();
+        let heavy: Vec<usize> = (0..len)
+            .into_par_iter()
+            .filter(|&i| scaled(weights[i]) >= 1.0)
+            .collect();
+
+        // `deficits[j]` is where the `j`th light's deficit starts, and
+        // `excesses[k]` where the `k`th heavy's excess starts.
+        let mut deficits: Vec<f64> = light
+            .par_iter()
+            .map(|&i| 1.0 - scaled(weights[i]))
+            .collect();
+        let total_deficit = prefix_sums(&mut deficits);
+        let mut excesses: Vec<f64> = heavy
+            .par_iter()
+            .map(|&i| scaled(weights[i]) - 1.0)
+            .collect();
+        prefix_sums(&mut excesses);
+
+        // Each chunk of columns finds where it starts in the lists by binary
+        // search, and then sweeps through them, since both the heavy that
+        // covers a light and the light that follows a heavy only move forward.
+        let mut alias = vec![0; len];
+        let mut probability = vec![0.0; len];
+        alias
+            .par_chunks_mut(CHUNK)
+            .zip(probability.par_chunks_mut(CHUNK))
+            .enumerate()
+            .for_each(|(chunk, (alias, probability))| {
+                let start = chunk * CHUNK;
+                let mut j = light.partition_point(|&i| i < start);
+                let mut k = heavy.partition_point(|&i| i < start);
+                let mut covering: Option<usize> = None;
+                let mut following: Option<usize> = None;
+                for (offset, (alias, probability)) in
+                    alias.iter_mut().zip(probability.iter_mut()).enumerate()
+                {
+                    let i = start + offset;
+                    let weight = scaled(weights[i]);
+                    if weight < 1.0 {
+                        let deficit = deficits[j];
+                        j += 1;
+                        // Rounding can leave no heavies at all, in which case
+                        // every column keeps all of its own probability.
+                        if heavy.is_empty() {
+                            *alias = i;
+                            *probability = 1.0;
+                            continue;
+                        }
+                        let mut h = covering.unwrap_or_else(|| {
+                            excesses.partition_point(|&excess| excess <= deficit) - 1
+                        });
+                        while h + 1 < heavy.len() && excesses[h + 1] <= deficit {
+                            h += 1;
+                        }
+                        covering = Some(h);
+                        *alias = heavy[h];
+                        *probability = weight;
+                    } else {
+                        let h = k;
+                        k += 1;
+                        if h + 1 == heavy.len() {
+                            *alias = i;
+                            *probability = 1.0;
+                            continue;
+                        }
+                        let end = excesses[h + 1];
+                        let mut next = following
+                            .unwrap_or_else(|| deficits.partition_point(|&deficit| deficit < end));
+                        while next < deficits.len() && deficits[next] < end {
+                            next += 1;
+                        }
+                        following = Some(next);
+                        let next_deficit = deficits.get(next).cloned().unwrap_or(total_deficit);
+                        *alias = heavy[h + 1];
+                        *probability = (1.0 - (next_deficit - end)).clamp(0.0, 1.0);
+                    }
+                }
+            });
+
+        Ok(RouletteIndex {
+            alias,
+            probability: Probability::Float(probability),
+            range: Some(Uniform::from(0..len)),
+            single_word: None,
+            underflow: index::underflow(weights, sum),
+        })
+    }
+}
+
+impl RouletteIndex {
+    /// Fills `dest` with random indices, drawn in parallel. The indices only
+    /// depend on `seed` and the length of `dest`, not on the number of
+    /// threads, and are the same between runs and platforms for the same
+    /// version of this crate.
+    ///
+    /// Panics if the `RouletteIndex` is empty and `dest` isn't.
+    pub fn par_fill_indices(&self, dest: &mut [usize], seed: u64) {
+        dest.par_chunks_mut(BLOCK)
+            .enumerate()
+            .for_each(|(block, dest)| {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                rng.set_stream(block as u64);
+                self.fill_indices(dest, &mut rng);
+            });
+    }
+}
+
+impl<T: Sync> Roulette<T> {
+    /// Returns `n` random elements, drawn in parallel; see
+    /// `RouletteIndex::par_fill_indices`.
+    ///
+    /// Panics if the `Roulette` is empty and `n` isn't 0.
+    pub fn par_sample_n(&self, n: usize, seed: u64) -> Vec<&T> {
+        let mut indices = vec![0; n];
+        self.index.par_fill_indices(&mut indices, seed);
+        indices
+            .into_par_iter()
+            .map(|index| &self.items[index])
+            .collect()
+    }
+}
+
+impl<T: Send> Roulette<T> {
+    /// Creates a `Roulette` like `Roulette::new`, but builds the alias table
+    /// in parallel; see `RouletteIndex::par_new`.
+    ///
+    /// Panics if the probabilities are invalid; see `Roulette::try_new`.
+    pub fn par_new(items: Vec<(T, f64)>) -> Roulette<T> {
+        match Roulette::try_par_new(items) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid probabilities in Roulette: {}", err),
+        }
+    }
+
+    /// Creates a `Roulette` like `Roulette::par_new`, but returns an error
+    /// instead of panicking if the probabilities are invalid.
+    pub fn try_par_new(items: Vec<(T, f64)>) -> Result<Roulette<T>, RouletteError> {
+        let (items, weights): (Vec<T>, Vec<f64>) = items.into_par_iter().unzip();
+        let index = RouletteIndex::try_par_new(&weights)?;
+        Ok(Roulette { items, index })
+    }
+}
+
+/// Checks the weights like `error::check_weights`, returning the same error
+/// for the same weights.
+fn check_weights(weights: &[f64]) -> Result<f64, RouletteError> {
+    let chunks: Vec<(Option<usize>, f64)> = weights
+        .par_chunks(CHUNK)
+        .map(|chunk| {
+            let invalid = chunk
+                .iter()
+                .position(|&weight| !(weight >= 0.0 && weight.is_finite()));
+            (invalid, chunk.iter().sum())
+        })
+        .collect();
+    let mut sum = 0.0;
+    for (chunk, &(invalid, chunk_sum)) in chunks.iter().enumerate() {
+        if let Some(offset) = invalid {
+            let index = chunk * CHUNK + offset;
+            error::check_weight(index, weights[index])?;
+        }
+        sum += chunk_sum;
+    }
+    if sum == 0.0 {
+        Err(RouletteError::ZeroSum)
+    } else if sum.is_infinite() {
+        Err(RouletteError::SumOverflow)
+    } else {
+        Ok(sum)
+    }
+}
+
+/// Replaces each value with the sum of the values before it, and returns the
+/// sum of all of them.
+fn prefix_sums(values: &mut [f64]) -> f64 {
+    let mut offsets: Vec<f64> = values
+        .par_chunks(CHUNK)
+        .map(|chunk| chunk.iter().sum())
+        .collect();
+    let mut total = 0.0;
+    for offset in &mut offsets {
+        let sum = *offset;
+        *offset = total;
+        total += sum;
+    }
+    values
+        .par_chunks_mut(CHUNK)
+        .zip(offsets)
+        .for_each(|(chunk, mut sum)| {
+            for value in chunk {
+                let x = *value;
+                *value = sum;
+                sum += x;
+            }
+        });
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use stats::tests::{assert_counts_fit, WEIGHTS};
+
+    #[test]
+    fn matches_serial() {
+        let mut rng = rand::thread_rng();
+        for &len in &[1, 2, 3, 10, 100, 1000, 3 * CHUNK + 7] {
+            let weights: Vec<f64> = (0..len)
+                .map(|_| {
+                    if rng.gen_bool(0.3) {
+                        0.0
+                    } else {
+                        rng.gen_range(0.0, 1e6)
+                    }
+                })
+                .collect();
+            if weights.iter().all(|&weight| weight == 0.0) {
+                continue;
+            }
+            let index = RouletteIndex::par_new(&weights);
+            assert!(index.max_error_vs_input(&weights) < 1e-12);
+            let serial = RouletteIndex::new(&weights).effective_probabilities();
+            for ((weight, probability), expected) in weights
+                .iter()
+                .zip(index.effective_probabilities())
+                .zip(serial)
+            {
+                assert!((probability - expected).abs() < 1e-12);
+                if *weight == 0.0 {
+                    assert_eq!(probability, 0.0);
+                }
+            }
+        }
+
+        let roulette = Roulette::par_new(vec![('a', 1.0), ('b', 1.0), ('c', 1.0)]);
+        assert_eq!(roulette.effective_probabilities(), vec![1.0 / 3.0; 3]);
+        assert!(RouletteIndex::par_new(&[]).is_empty());
+        let weights = [1e300, 1e-300, 0.0];
+        assert_eq!(
+            RouletteIndex::par_new(&weights).underflow,
+            RouletteIndex::new(&weights).underflow
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut weights = vec![1.0; 2 * CHUNK];
+        weights[CHUNK + 5] = -1.0;
+        weights[CHUNK + 9] = f64::NAN;
+        assert_eq!(
+            RouletteIndex::try_par_new(&weights).err(),
+            RouletteIndex::try_new(&weights).err()
+        );
+        for weights in &[vec![0.0; 3], vec![f64::MAX; 3]] {
+            assert_eq!(
+                RouletteIndex::try_par_new(weights).err(),
+                RouletteIndex::try_new(weights).err()
+            );
+        }
+    }
+
+    #[test]
+    fn par_sample_n() {
+        let roulette = Roulette::new(WEIGHTS.iter().map(|&weight| ((), weight)).collect());
+        let mut indices = vec![0; 3 * BLOCK + 5];
+        roulette.as_index().par_fill_indices(&mut indices, 19);
+        assert_counts_fit(&::stats::count_indices(
+            WEIGHTS.len(),
+            indices.iter().cloned(),
+        ));
+
+        // The samples don't depend on the number of threads.
+        for &threads in &[1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut same = vec![0; indices.len()];
+            pool.install(|| roulette.as_index().par_fill_indices(&mut same, 19));
+            assert_eq!(indices, same);
+        }
+        let mut other = vec![0; indices.len()];
+        roulette.as_index().par_fill_indices(&mut other, 20);
+        assert_ne!(indices, other);
+
+        let roulette = Roulette::new(vec![('a', 0.0), ('b', 1.0)]);
+        assert_eq!(roulette.par_sample_n(100, 1), vec![&'b'; 100]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Resampling schemes for particle filters and other sequential Monte Carlo
+//! methods.
+//!
+//! Each function takes the particles' weights and returns the indices of `n`
+//! particles chosen in proportion to them. Drawing the indices independently,
+//! as `multinomial` does, adds more variance than necessary; the other schemes
+//! keep each particle's count closer to its expected value `n * w / sum`:
+//!
+//! - `stratified` draws one uniform in each of `n` equal strata of `0..1`.
+//! - `systematic` uses one uniform, shifted into each stratum. Each count is
+//!   then the expected count rounded up or down.
+//! - `residual` copies each particle the whole number of times it's expected,
+//!   and draws the rest independently from the remainders.
+//!
+//! All of them panic if `weights` is empty, if the weights are all zero or
+//! their sum overflows, or if any weight is negative, NaN or infinite, even
+//! when `n` is zero.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate rand;
+//! extern crate roulette;
+//!
+//! use rand::rngs::StdRng;
+//! use rand::SeedableRng;
+//! use roulette::resample;
+//!
+//! fn main() {
+//!     let weights = [0.1, 0.0, 0.6, 0.3];
+//!     let indices = resample::systematic(&weights, 10, &mut StdRng::seed_from_u64(0));
+//!     assert_eq!(indices.iter().filter(|&&i| i == 2).count(), 6);
+//! }
+//! ```
+
+use alloc::vec::Vec;
+use rand::Rng;
+
+use error;
+use RouletteIndex;
+
+/// Returns `n` indices drawn independently, in the order they were drawn.
+pub fn multinomial<R: Rng + ?Sized>(weights: &[f64], n: usize, rng: &mut R) -> Vec<usize> {
+    check(weights);
+    let index = RouletteIndex::new(weights);
+    (0..n).map(|_| index.sample(rng)).collect()
+}
+
+/// Returns `n` indices in ascending order, chosen by one uniform in each of
+/// `n` equal strata of the cumulative weights.
+pub fn stratified<R: Rng + ?Sized>(weights: &[f64], n: usize, rng: &mut R) -> Vec<usize> {
+    let sum = check(weights);
+    let points = (0..n).map(|i| (i as f64 + rng.gen::<f64>()) / n as f64);
+    walk(weights, sum, points)
+}
+
+/// Returns `n` indices in ascending order, chosen by a single uniform offset
+/// repeated in each of `n` equal strata of the cumulative weights.
+pub fn systematic<R: Rng + ?Sized>(weights: &[f64], n: usize, rng: &mut R) -> Vec<usize> {
+    let sum = check(weights);
+    let offset = rng.gen::<f64>();
+    let points = (0..n).map(|i| (i as f64 + offset) / n as f64);
+    walk(weights, sum, points)
+}
+
+/// Returns `n` indices: first each index the whole number of times it's
+/// expected, in ascending order, then the rest drawn independently with
+/// probabilities proportional to the fractional parts.
+pub fn residual<R: Rng + ?Sized>(weights: &[f64], n: usize, rng: &mut R) -> Vec<usize> {
+    let sum = check(weights);
+    let mut indices = Vec::with_capacity(n);
+    let mut remainders = Vec::with_capacity(weights.len());
+    for (i, &weight) in weights.iter().enumerate() {
+        let expected = n as f64 * (weight / sum);
+        let copies = expected as usize;
+        for _ in 0..copies {
+            indices.push(i);
+        }
+        remainders.push(expected - copies as f64);
+    }
+    // Rounding can make the whole parts add up to slightly more than `n`.
+    indices.truncate(n);
+    let rest = n - indices.len();
+    if rest > 0 {
+        // If rounding left no remainders at all, fall back to the weights.
+        let index =
+            RouletteIndex::try_new(&remainders).unwrap_or_else(|_| RouletteIndex::new(weights));
+        indices.extend((0..rest).map(|_| index.sample(rng)));
+    }
+    indices
+}
+
+fn check(weights: &[f64]) -> f64 {
+    match error::check_weights(weights.iter().cloned()) {
+        Ok(sum) => sum,
+        Err(err) => panic!("Invalid weights for resampling: {}", err),
+    }
+}
+
+/// Returns the index whose range of the cumulative weights contains each of
+/// `points`, which must be ascending fractions of the total. Indices with a
+/// weight of zero are never returned, even if the sum was rounded.
+fn walk<I>(weights: &[f64], sum: f64, points: I) -> Vec<usize>
+where
+    I: ExactSizeIterator<Item = f64>,
+{
+    let last = weights
+        .iter()
+        .rposition(|&weight| weight > 0.0)
+        .expect("Weights must not all be zero");
+    let mut indices = Vec::with_capacity(points.len());
+    let mut index = 0;
+    let mut cumulative = weights[0];
+    for point in points {
+        let target = point * sum;
+        while target >= cumulative && index < last {
+            index += 1;
+            cumulative += weights[index];
+        }
+        indices.push(index);
+    }
+    indices
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use stats;
+
+    type Scheme = fn(&[f64], usize, &mut StdRng) -> Vec<usize>;
+
+    const WEIGHTS: [f64; 6] = [0.0, 3.0, 1.0, 0.5, 0.0, 2.5];
+
+    fn counts(indices: &[usize]) -> Vec<u64> {
+        let mut counts = vec![0; WEIGHTS.len()];
+        for &index in indices {
+            counts[index] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn counts_match_weights() {
+        let mut rng = StdRng::seed_from_u64(25);
+        let schemes: [Scheme; 4] = [multinomial, stratified, systematic, residual];
+        for scheme in &schemes {
+            let indices = scheme(&WEIGHTS, 100_000, &mut rng);
+            assert_eq!(indices.len(), 100_000);
+            let counts = counts(&indices);
+            assert_eq!((counts[0], counts[4]), (0, 0));
+            assert!(stats::chi_squared(&counts, &WEIGHTS).p_value > 0.001);
+            assert!(scheme(&WEIGHTS, 0, &mut rng).is_empty());
+        }
+
+        // Each count is the expected count of 10 * w / 7, rounded either way.
+        for _ in 0..100 {
+            let counts = counts(&systematic(&WEIGHTS, 10, &mut rng));
+            for (&count, &weight) in counts.iter().zip(&WEIGHTS) {
+                let expected = 10.0 * weight / 7.0;
+                assert!(count as f64 > expected - 1.0 && (count as f64) < expected + 1.0);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "no probabilities were given")]
+    fn multinomial_empty() {
+        multinomial(&[], 0, &mut StdRng::seed_from_u64(25));
+    }
+
+    #[test]
+    fn variance() {
+        let mut rng = StdRng::seed_from_u64(25);
+        let (n, trials) = (50, 2000);
+        let mut variance = |scheme: Scheme| -> f64 {
+            // The variance of each count around its expected value, summed.
+            let mut total = 0.0;
+            for _ in 0..trials {
+                let counts = counts(&scheme(&WEIGHTS, n, &mut rng));
+                for (&count, &weight) in counts.iter().zip(&WEIGHTS) {
+                    let error = count as f64 - n as f64 * weight / 7.0;
+                    total += error * error;
+                }
+            }
+            total / trials as f64
+        };
+        let multinomial = variance(multinomial);
+        // For multinomial draws, the sum is n * (1 - sum of p^2).
+        let expected: f64 = WEIGHTS.iter().map(|w| w / 7.0 * (1.0 - w / 7.0)).sum();
+        assert!((multinomial / (n as f64 * expected) - 1.0).abs() < 0.1);
+        for &scheme in &[stratified as Scheme, systematic, residual] {
+            assert!(variance(scheme) < multinomial / 2.0);
+        }
+    }
+}
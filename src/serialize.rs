@@ -0,0 +1,200 @@
+use alloc::vec::Vec;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use index::{Probability, StoredProbability};
+use {Roulette, RouletteIndex};
+
+// The alias tables are stored as-is so they don't have to be rebuilt, but
+// they're validated when deserializing so that a corrupted or tampered table
+// can't make `sample` index out of bounds.
+
+#[derive(Serialize)]
+#[serde(rename = "Roulette")]
+struct RouletteRef<'a, T: 'a> {
+    items: &'a [T],
+    alias: &'a [usize],
+    probability: ProbabilityRef<'a>,
+    underflow: &'a [(usize, f64)],
+}
+
+#[derive(Serialize)]
+#[serde(rename = "RouletteIndex")]
+struct RouletteIndexRef<'a> {
+    alias: &'a [usize],
+    probability: ProbabilityRef<'a>,
+    underflow: &'a [(usize, f64)],
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Probability")]
+enum ProbabilityRef<'a> {
+    Float(&'a [f64]),
+    Exact {
+        numerators: &'a [u64],
+        denominator: u64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Roulette")]
+struct RouletteData<T> {
+    items: Vec<T>,
+    alias: Vec<usize>,
+    probability: ProbabilityData,
+    // Tables serialized before this was added have no underflowed weights.
+    #[serde(default)]
+    underflow: Vec<(usize, f64)>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "RouletteIndex")]
+struct RouletteIndexData {
+    alias: Vec<usize>,
+    probability: ProbabilityData,
+    #[serde(default)]
+    underflow: Vec<(usize, f64)>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Probability")]
+enum ProbabilityData {
+    Float(Vec<f64>),
+    Exact {
+        numerators: Vec<u64>,
+        denominator: u64,
+    },
+}
+
+impl<'a> From<&'a Probability> for ProbabilityRef<'a> {
+    fn from(probability: &'a Probability) -> ProbabilityRef<'a> {
+        match *probability {
+            Probability::Float(ref probability) => ProbabilityRef::Float(probability),
+            Probability::Exact {
+                ref numerators,
+                denominator,
+                ..
+            } => ProbabilityRef::Exact {
+                numerators,
+                denominator,
+            },
+        }
+    }
+}
+
+/// Validates a deserialized alias table; see `RouletteIndex::from_parts`.
+fn to_index(
+    alias: Vec<usize>,
+    probability: ProbabilityData,
+    underflow: Vec<(usize, f64)>,
+) -> Result<RouletteIndex, &'static str> {
+    let probability = match probability {
+        ProbabilityData::Float(probability) => StoredProbability::Float(probability),
+        ProbabilityData::Exact {
+            numerators,
+            denominator,
+        } => StoredProbability::Exact {
+            numerators,
+            denominator,
+        },
+    };
+    RouletteIndex::from_parts(alias, probability, underflow)
+}
+
+impl<T: Serialize> Serialize for Roulette<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RouletteRef {
+            items: &self.items,
+            alias: &self.index.alias,
+            probability: ProbabilityRef::from(&self.index.probability),
+            underflow: &self.index.underflow,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Roulette<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Roulette<T>, D::Error> {
+        let data = RouletteData::deserialize(deserializer)?;
+        if data.alias.len() != data.items.len() {
+            return Err(D::Error::custom(
+                "Roulette alias table has the wrong length",
+            ));
+        }
+        let index =
+            to_index(data.alias, data.probability, data.underflow).map_err(D::Error::custom)?;
+        Ok(Roulette {
+            items: data.items,
+            index,
+        })
+    }
+}
+
+impl Serialize for RouletteIndex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RouletteIndexRef {
+            alias: &self.alias,
+            probability: ProbabilityRef::from(&self.probability),
+            underflow: &self.underflow,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RouletteIndex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RouletteIndex, D::Error> {
+        let data = RouletteIndexData::deserialize(deserializer)?;
+        to_index(data.alias, data.probability, data.underflow).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let roulette = Roulette::new(vec![('a', 1.0), ('b', 0.0), ('c', 2.0)]);
+        let json = serde_json::to_string(&roulette).unwrap();
+        let roulette: Roulette<char> = serde_json::from_str(&json).unwrap();
+        assert!(roulette.max_error_vs_input(&[1.0, 0.0, 2.0]) < 1e-15);
+
+        let roulette = Roulette::from_integer_weights(vec![('a', 1), ('b', 0), ('c', 2)]);
+        let json = serde_json::to_string(&roulette).unwrap();
+        let roulette: Roulette<char> = serde_json::from_str(&json).unwrap();
+        assert_eq!(roulette.max_error_vs_input(&[1.0, 0.0, 2.0]), 0.0);
+
+        let roulette = Roulette::new(vec![('a', 1e300), ('b', 1e-300)]);
+        let json = serde_json::to_string(&roulette).unwrap();
+        let roulette: Roulette<char> = serde_json::from_str(&json).unwrap();
+        assert_eq!(roulette.index.underflow, vec![(1, 1e-300)]);
+
+        let roulette: Roulette<char> = Roulette::empty();
+        let json = serde_json::to_string(&roulette).unwrap();
+        let roulette: Roulette<char> = serde_json::from_str(&json).unwrap();
+        assert!(roulette.is_empty());
+    }
+
+    #[test]
+    fn tampered() {
+        let tampered = [
+            r#"{"items":["a","b"],"alias":[0,2],"probability":{"Float":[0.5,1.0]}}"#,
+            r#"{"items":["a","b"],"alias":[1],"probability":{"Float":[0.5,1.0]}}"#,
+            r#"{"items":["a","b"],"alias":[1,0],"probability":{"Float":[0.5]}}"#,
+            r#"{"items":["a","b"],"alias":[1,0],"probability":{"Float":[0.5,1.5]}}"#,
+            r#"{"items":["a","b"],"alias":[1,0],"probability":{"Exact":{"numerators":[1,2],"denominator":0}}}"#,
+            r#"{"items":["a","b"],"alias":[1,0],"probability":{"Exact":{"numerators":[1,3],"denominator":2}}}"#,
+            r#"{"items":["a","b"],"alias":[1,1],"probability":{"Float":[0.5,1.0]},"underflow":[[2,1.0]]}"#,
+        ];
+        for json in &tampered {
+            assert!(serde_json::from_str::<Roulette<char>>(json).is_err());
+        }
+        let index = r#"{"alias":[0,3,1],"probability":{"Float":[1.0,1.0,1.0]}}"#;
+        assert!(serde_json::from_str::<RouletteIndex>(index).is_err());
+
+        let valid = r#"{"items":["a","b"],"alias":[1,1],"probability":{"Float":[0.5,1.0]}}"#;
+        assert!(serde_json::from_str::<Roulette<char>>(valid).is_ok());
+    }
+}
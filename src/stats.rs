@@ -0,0 +1,358 @@
+//! Goodness-of-fit tests for checking that a `Roulette` returns elements with
+//! the probabilities it was built from.
+//!
+//! Samples are drawn with a seeded `StdRng`, so a test gives the same result
+//! every time it's run with the same seed and version of `rand`.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate roulette;
+//!
+//! use roulette::{stats, Roulette};
+//!
+//! fn main() {
+//!     let weights = [1.0, 2.0, 0.0, 5.0];
+//!     let roulette = Roulette::new(vec![('a', 1.0), ('b', 2.0), ('c', 0.0), ('d', 5.0)]);
+//!     let counts = stats::sample_counts(4, 100_000, 42, |rng| roulette.sample_index(rng));
+//!     let result = stats::chi_squared(&counts, &weights);
+//!     assert!(result.p_value > 0.001);
+//! }
+//! ```
+
+use std::f64::consts::PI;
+use std::vec::Vec;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use error;
+
+/// The result of a goodness-of-fit test.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GoodnessOfFit {
+    /// The test statistic.
+    pub statistic: f64,
+    /// The probability of a statistic at least this large if the samples
+    /// really came from the expected distribution.
+    pub p_value: f64,
+    /// The Pearson residual `(observed - expected) / sqrt(expected)` of each
+    /// bucket. A bucket with no expected samples has a residual of 0 if it
+    /// has no samples and infinity otherwise.
+    pub residuals: Vec<f64>,
+}
+
+/// Calls `sample` `samples` times with a `StdRng` seeded with `seed`, and
+/// returns how many times each index below `len` was returned.
+///
+/// `sample` can draw from any of the samplers in this crate, such as
+/// `|rng| index.sample(rng)` for a `RouletteIndex`.
+///
+/// Panics if `sample` returns an index of `len` or more.
+pub fn sample_counts<F>(len: usize, samples: usize, seed: u64, mut sample: F) -> Vec<u64>
+where
+    F: FnMut(&mut StdRng) -> usize,
+{
+    let mut rng = StdRng::seed_from_u64(seed);
+    count_indices(len, (0..samples).map(|_| sample(&mut rng)))
+}
+
+/// Returns how many times each index below `len` appears in `indices`, for
+/// samples that were drawn all at once, such as by `fill_indices`.
+///
+/// Panics if an index is `len` or more.
+pub fn count_indices<I: IntoIterator<Item = usize>>(len: usize, indices: I) -> Vec<u64> {
+    let mut counts = vec![0; len];
+    for index in indices {
+        counts[index] += 1;
+    }
+    counts
+}
+
+/// Runs Pearson's chi-squared test of whether `counts` were drawn with
+/// probabilities proportional to `weights`.
+///
+/// Buckets with a weight of zero don't count towards the degrees of freedom;
+/// any samples in them give a p-value of 0. Like any chi-squared test, this
+/// is only accurate if every bucket with a non-zero weight expects at least
+/// about 5 samples.
+///
+/// Panics if `counts` and `weights` have different lengths, if there are no
+/// samples, or if the weights are invalid (see `Roulette::try_new`).
+pub fn chi_squared(counts: &[u64], weights: &[f64]) -> GoodnessOfFit {
+    let expected = expected_counts(counts, weights);
+    let statistic = counts
+        .iter()
+        .zip(&expected)
+        .map(|(&observed, &expected)| match expected {
+            0.0 => zero_bucket(observed),
+            _ => (observed as f64 - expected).powi(2) / expected,
+        })
+        .sum();
+    goodness_of_fit(counts, &expected, statistic)
+}
+
+/// Runs a G-test (a likelihood-ratio test) of whether `counts` were drawn
+/// with probabilities proportional to `weights`. This is usually more
+/// accurate than `chi_squared` when some buckets expect few samples.
+///
+/// Panics under the same conditions as `chi_squared`.
+pub fn g_test(counts: &[u64], weights: &[f64]) -> GoodnessOfFit {
+    let expected = expected_counts(counts, weights);
+    let sum: f64 = counts
+        .iter()
+        .zip(&expected)
+        .map(|(&observed, &expected)| match (observed, expected) {
+            (0, _) => 0.0,
+            (_, 0.0) => zero_bucket(observed),
+            _ => observed as f64 * (observed as f64 / expected).ln(),
+        })
+        .sum();
+    goodness_of_fit(counts, &expected, 2.0 * sum)
+}
+
+/// Runs a Kolmogorov-Smirnov test of whether `counts` were drawn with
+/// probabilities proportional to `weights`, comparing the cumulative
+/// distributions in index order. The statistic is the largest difference
+/// between them.
+///
+/// This uses the asymptotic distribution for continuous data, which makes the
+/// p-value conservative (too large) for discrete data, but unlike the
+/// chi-squared test it's still meaningful when each bucket expects only a few
+/// samples.
+///
+/// Panics under the same conditions as `chi_squared`.
+pub fn kolmogorov_smirnov(counts: &[u64], weights: &[f64]) -> GoodnessOfFit {
+    let expected = expected_counts(counts, weights);
+    let total: u64 = counts.iter().sum();
+    let total = total as f64;
+
+    let mut observed_cdf = 0.0;
+    let mut expected_cdf = 0.0;
+    let mut statistic: f64 = 0.0;
+    for (&observed, &expected) in counts.iter().zip(&expected) {
+        observed_cdf += observed as f64;
+        expected_cdf += expected;
+        statistic = statistic.max((observed_cdf - expected_cdf).abs() / total);
+    }
+
+    let sqrt_total = total.sqrt();
+    let lambda = (sqrt_total + 0.12 + 0.11 / sqrt_total) * statistic;
+    GoodnessOfFit {
+        statistic,
+        p_value: kolmogorov_q(lambda),
+        residuals: residuals(counts, &expected),
+    }
+}
+
+/// Returns the number of samples expected in each bucket.
+fn expected_counts(counts: &[u64], weights: &[f64]) -> Vec<f64> {
+    assert_eq!(counts.len(), weights.len(), "Wrong number of weights");
+    let total: u64 = counts.iter().sum();
+    assert!(total > 0, "No samples to test");
+    let sum = match error::check_weights(weights.iter().cloned()) {
+        Ok(sum) => sum,
+        Err(err) => panic!("Invalid weights: {}", err),
+    };
+    weights
+        .iter()
+        .map(|weight| weight / sum * total as f64)
+        .collect()
+}
+
+/// The contribution to a statistic of a bucket that expects no samples.
+fn zero_bucket(observed: u64) -> f64 {
+    if observed == 0 {
+        0.0
+    } else {
+        f64::INFINITY
+    }
+}
+
+fn residuals(counts: &[u64], expected: &[f64]) -> Vec<f64> {
+    counts
+        .iter()
+        .zip(expected)
+        .map(|(&observed, &expected)| match expected {
+            0.0 => zero_bucket(observed),
+            _ => (observed as f64 - expected) / expected.sqrt(),
+        })
+        .collect()
+}
+
+/// Builds the result of a test whose statistic follows a chi-squared
+/// distribution.
+fn goodness_of_fit(counts: &[u64], expected: &[f64], statistic: f64) -> GoodnessOfFit {
+    let buckets = expected.iter().filter(|&&expected| expected > 0.0).count();
+    let degrees_of_freedom = (buckets - 1) as f64;
+    let p_value = if statistic.is_infinite() {
+        0.0
+    } else if degrees_of_freedom == 0.0 {
+        1.0
+    } else {
+        gamma_q(degrees_of_freedom / 2.0, statistic / 2.0)
+    };
+    GoodnessOfFit {
+        statistic,
+        p_value,
+        residuals: residuals(counts, expected),
+    }
+}
+
+/// Returns the complement of the Kolmogorov distribution's CDF at `lambda`.
+fn kolmogorov_q(lambda: f64) -> f64 {
+    if lambda < 0.2 {
+        // The series converges too slowly here, and the result is 1 anyway.
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    let mut sign = 2.0;
+    for j in 1..=100 {
+        let term = sign * (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += term;
+        if term.abs() < 1e-16 {
+            break;
+        }
+        sign = -sign;
+    }
+    sum.clamp(0.0, 1.0)
+}
+
+/// Returns the regularized upper incomplete gamma function Q(a, x), using a
+/// series for small `x` and a continued fraction otherwise.
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-15;
+    const MAX_ITERATIONS: usize = 10_000;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefactor = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..MAX_ITERATIONS {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        1.0 - sum * log_prefactor.exp()
+    } else {
+        // Lentz's method.
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..MAX_ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        log_prefactor.exp() * h
+    }
+}
+
+/// Returns the natural log of the gamma function, using the Lanczos
+/// approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // The reflection formula.
+        (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x)
+    } else {
+        let x = x - 1.0;
+        let mut sum = COEFFICIENTS[0];
+        for (i, &coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+            sum += coefficient / (x + i as f64);
+        }
+        let t = x + 7.5;
+        0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use RouletteIndex;
+
+    /// Weights for checking each sampler against, with a zero weight, a tiny
+    /// one and one that dominates.
+    pub(crate) const WEIGHTS: [f64; 7] = [3.0, 0.0, 1.0, 0.5, 10.0, 1e-3, 2.0];
+
+    /// Draws 200,000 indices with `sample` and checks that they fit `WEIGHTS`.
+    pub(crate) fn assert_fits<F>(seed: u64, sample: F)
+    where
+        F: FnMut(&mut StdRng) -> usize,
+    {
+        assert_counts_fit(&sample_counts(WEIGHTS.len(), 200_000, seed, sample));
+    }
+
+    /// Checks that the counts of indices drawn from a sampler fit `WEIGHTS`.
+    pub(crate) fn assert_counts_fit(counts: &[u64]) {
+        assert_eq!(counts[1], 0);
+        assert!(chi_squared(counts, &WEIGHTS).p_value > 0.001);
+        assert!(g_test(counts, &WEIGHTS).p_value > 0.001);
+        assert!(kolmogorov_smirnov(counts, &WEIGHTS).p_value > 0.001);
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn p_values() {
+        assert_close(gamma_q(0.5, 3.841_459 / 2.0), 0.05);
+        assert_close(gamma_q(5.0, 18.307_038 / 2.0), 0.05);
+        assert_close(gamma_q(50.0, 124.342_113 / 2.0), 0.05);
+        assert_close(gamma_q(2.0, 0.5), 0.909_796_7);
+        assert_close(kolmogorov_q(1.358_099), 0.05);
+        assert_close(ln_gamma(10.0), 362_880f64.ln());
+    }
+
+    #[test]
+    fn detects_wrong_weights() {
+        let index = RouletteIndex::new(&[1.0, 1.0, 1.0, 1.2]);
+        let counts = sample_counts(4, 200_000, 1, |rng| index.sample(rng));
+        let weights = [1.0, 1.0, 1.0, 1.0];
+        assert!(chi_squared(&counts, &weights).p_value < 1e-6);
+        assert!(g_test(&counts, &weights).p_value < 1e-6);
+        assert!(kolmogorov_smirnov(&counts, &weights).p_value < 1e-6);
+
+        let result = chi_squared(&[0, 10, 1], &[0.0, 1.0, 0.0]);
+        assert_eq!(result.p_value, 0.0);
+        assert_eq!(result.residuals[2], f64::INFINITY);
+    }
+}
@@ -0,0 +1,339 @@
+//! Sampling tokens from a language model's logits, with the filters commonly
+//! used for text generation.
+//!
+//! The filters are applied in this order: the repetition penalty, the
+//! temperature, top-k, min-p and finally top-p. Only the tokens that survive
+//! them go into the alias table, and top-k and top-p use partial selection
+//! rather than sorting the whole vocabulary, so building a sampler for a large
+//! vocabulary costs little more than one pass over the logits.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate rand;
+//! extern crate roulette;
+//!
+//! use roulette::tokens::SamplingConfig;
+//!
+//! fn main() {
+//!     let logits = [1.0, 3.0, 2.5, -1.0, 0.5];
+//!     let config = SamplingConfig::new().temperature(0.8).top_k(3).top_p(0.9);
+//!     let sampler = config.sampler(&logits, &[1]);
+//!     let token = *sampler.sample(&mut rand::thread_rng());
+//!     assert!(token == 0 || token == 1 || token == 2);
+//! }
+//! ```
+
+use std::vec::Vec;
+
+use rand::Rng;
+
+use error::RouletteError;
+use logits::softmax_weights;
+use {Roulette, RouletteIndex};
+
+/// The number of tokens top-p sorts at first, doubling each time they don't
+/// hold enough of the probability.
+const TOP_P_CHUNK: usize = 64;
+
+/// The filters to apply to logits before sampling a token.
+///
+/// By default, no filter is applied and the temperature is 1, so tokens are
+/// sampled from the softmax of the logits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SamplingConfig {
+    temperature: f64,
+    top_k: Option<usize>,
+    top_p: f64,
+    min_p: f64,
+    repetition_penalty: f64,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> SamplingConfig {
+        SamplingConfig::new()
+    }
+}
+
+impl SamplingConfig {
+    /// Creates a `SamplingConfig` that doesn't filter any tokens.
+    pub fn new() -> SamplingConfig {
+        SamplingConfig {
+            temperature: 1.0,
+            top_k: None,
+            top_p: 1.0,
+            min_p: 0.0,
+            repetition_penalty: 1.0,
+        }
+    }
+
+    /// Divides the logits by `temperature`. Building a sampler fails with
+    /// `RouletteError::InvalidTemperature` unless it's positive and finite.
+    pub fn temperature(mut self, temperature: f64) -> SamplingConfig {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Keeps only the `k` most likely tokens. Ties are broken arbitrarily.
+    ///
+    /// Panics if `k` is 0.
+    pub fn top_k(mut self, k: usize) -> SamplingConfig {
+        assert!(k > 0, "top_k must be at least 1");
+        self.top_k = Some(k);
+        self
+    }
+
+    /// Keeps only the most likely tokens whose probabilities add up to at
+    /// least `p`, also known as nucleus sampling.
+    ///
+    /// Panics unless `p` is in `(0, 1]`.
+    pub fn top_p(mut self, p: f64) -> SamplingConfig {
+        assert!(p > 0.0 && p <= 1.0, "top_p must be in (0, 1]");
+        self.top_p = p;
+        self
+    }
+
+    /// Keeps only the tokens that are at least `p` times as likely as the most
+    /// likely token.
+    ///
+    /// Panics unless `p` is in `[0, 1]`.
+    pub fn min_p(mut self, p: f64) -> SamplingConfig {
+        assert!((0.0..=1.0).contains(&p), "min_p must be in [0, 1]");
+        self.min_p = p;
+        self
+    }
+
+    /// Penalizes tokens that have already been generated: their logits are
+    /// divided by `penalty` if they're positive and multiplied by it
+    /// otherwise. A token is penalized once however often it was repeated.
+    ///
+    /// Panics unless `penalty` is positive and finite.
+    pub fn repetition_penalty(mut self, penalty: f64) -> SamplingConfig {
+        assert!(
+            penalty > 0.0 && penalty.is_finite(),
+            "repetition_penalty must be positive and finite"
+        );
+        self.repetition_penalty = penalty;
+        self
+    }
+
+    /// Builds a `Roulette` over the token ids that pass the filters, given the
+    /// logits for each token and the tokens generated so far. It can be
+    /// sampled any number of times while the logits stay the same.
+    ///
+    /// Panics if the logits or the temperature are invalid; see
+    /// `SamplingConfig::try_sampler`.
+    pub fn sampler(&self, logits: &[f64], previous: &[usize]) -> Roulette<usize> {
+        match self.try_sampler(logits, previous) {
+            Ok(roulette) => roulette,
+            Err(err) => panic!("Invalid logits in Roulette: {}", err),
+        }
+    }
+
+    /// Builds a `Roulette` like `SamplingConfig::sampler`, but returns an error
+    /// instead of panicking if any logit is NaN or positive infinity, if they're
+    /// all negative infinity, or if the temperature isn't positive and finite.
+    ///
+    /// Empty logits give an empty `Roulette`.
+    ///
+    /// Panics if a previous token is out of range for the logits.
+    pub fn try_sampler(
+        &self,
+        logits: &[f64],
+        previous: &[usize],
+    ) -> Result<Roulette<usize>, RouletteError> {
+        let mut penalized;
+        let mut logits = logits;
+        if self.repetition_penalty != 1.0 && !previous.is_empty() {
+            let mut previous = previous.to_vec();
+            previous.sort_unstable();
+            previous.dedup();
+            penalized = logits.to_vec();
+            for &token in &previous {
+                assert!(
+                    token < logits.len(),
+                    "Previous token {} is out of range",
+                    token
+                );
+                let logit = &mut penalized[token];
+                if *logit > 0.0 {
+                    *logit /= self.repetition_penalty;
+                } else {
+                    *logit *= self.repetition_penalty;
+                }
+            }
+            logits = &penalized;
+        }
+
+        // The weights are scaled so that the most likely token has weight 1,
+        // which makes min-p a simple threshold.
+        let weights = softmax_weights(logits, self.temperature)?;
+        let mut candidates: Vec<(usize, f64)> = weights
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, weight)| weight > 0.0 && weight >= self.min_p)
+            .collect();
+        if let Some(k) = self.top_k {
+            if k < candidates.len() {
+                candidates.select_nth_unstable_by(k - 1, descending);
+                candidates.truncate(k);
+            }
+        }
+        if self.top_p < 1.0 {
+            top_p(&mut candidates, self.top_p);
+        }
+
+        let (tokens, weights): (Vec<usize>, Vec<f64>) = candidates.into_iter().unzip();
+        let index = RouletteIndex::try_new(&weights)?;
+        Ok(Roulette {
+            items: tokens,
+            index,
+        })
+    }
+
+    /// Samples a single token id from the logits; see
+    /// `SamplingConfig::sampler`.
+    ///
+    /// Panics if the logits are empty or invalid.
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        logits: &[f64],
+        previous: &[usize],
+        rng: &mut R,
+    ) -> usize {
+        match self.try_sample(logits, previous, rng) {
+            Ok(token) => token,
+            Err(err) => panic!("Invalid logits in Roulette: {}", err),
+        }
+    }
+
+    /// Samples a single token id like `SamplingConfig::sample`, but returns an
+    /// error instead of panicking, including `RouletteError::Empty` if there
+    /// are no logits.
+    pub fn try_sample<R: Rng + ?Sized>(
+        &self,
+        logits: &[f64],
+        previous: &[usize],
+        rng: &mut R,
+    ) -> Result<usize, RouletteError> {
+        let roulette = self.try_sampler(logits, previous)?;
+        roulette
+            .try_sample(rng)
+            .cloned()
+            .ok_or(RouletteError::Empty)
+    }
+}
+
+fn descending(a: &(usize, f64), b: &(usize, f64)) -> core::cmp::Ordering {
+    b.1.total_cmp(&a.1)
+}
+
+/// Keeps the smallest set of most likely candidates whose weights add up to
+/// at least `p` of the total, sorting them in chunks so that only about as
+/// many candidates as are kept get sorted.
+fn top_p(candidates: &mut Vec<(usize, f64)>, p: f64) {
+    let total: f64 = candidates.iter().map(|&(_, weight)| weight).sum();
+    let target = p * total;
+    let mut sum = 0.0;
+    let mut sorted = 0;
+    let mut chunk = TOP_P_CHUNK;
+    while sorted < candidates.len() {
+        let rest = &mut candidates[sorted..];
+        let len = chunk.min(rest.len());
+        if len < rest.len() {
+            rest.select_nth_unstable_by(len - 1, descending);
+        }
+        rest[..len].sort_unstable_by(descending);
+        let end = sorted + len;
+        for i in sorted..end {
+            sum += candidates[i].1;
+            if sum >= target {
+                candidates.truncate(i + 1);
+                return;
+            }
+        }
+        sorted = end;
+        chunk *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(roulette: &Roulette<usize>) -> Vec<usize> {
+        let mut tokens = roulette.items().to_vec();
+        tokens.sort_unstable();
+        tokens
+    }
+
+    #[test]
+    fn filters() {
+        let logits: Vec<f64> = (0..1000).map(|i| (i % 100) as f64 / 10.0).collect();
+
+        let roulette = SamplingConfig::new().top_k(20).sampler(&logits, &[]);
+        assert_eq!(roulette.len(), 20);
+        assert!(roulette.items().iter().all(|&token| token % 100 >= 98));
+
+        // The ten tokens with each of the logits 9.9, 9.8 and 9.7 have 25.9% of
+        // the probability together, and those with 9.6 have 0.7% each.
+        let roulette = SamplingConfig::new().top_p(0.3).sampler(&logits, &[]);
+        assert_eq!(roulette.len(), 36);
+        assert!(roulette.items().iter().all(|&token| token % 100 >= 96));
+
+        let roulette = SamplingConfig::new()
+            .min_p(0.5)
+            .temperature(0.5)
+            .sampler(&logits, &[]);
+        // exp((l - 9.9) / 0.5) >= 0.5 keeps the logits 9.6 to 9.9.
+        assert_eq!(roulette.len(), 40);
+
+        let roulette = SamplingConfig::new()
+            .top_k(1)
+            .sampler(&[1.0, 3.0, 2.0], &[]);
+        assert_eq!(tokens(&roulette), vec![1]);
+        let roulette = SamplingConfig::new().top_p(0.5).sampler(&[0.0; 1000], &[]);
+        assert!(roulette.len() == 500 || roulette.len() == 501);
+        let roulette = SamplingConfig::new().top_p(1e-9).sampler(&logits, &[]);
+        assert_eq!(roulette.len(), 1);
+    }
+
+    #[test]
+    fn repetition_penalty() {
+        let config = SamplingConfig::new().repetition_penalty(2.0).top_k(2);
+        let roulette = config.sampler(&[3.0, 2.5, 2.0, -1.0], &[0, 0]);
+        assert_eq!(tokens(&roulette), vec![1, 2]);
+        let probabilities = roulette.effective_probabilities();
+        let token = roulette
+            .items()
+            .iter()
+            .position(|&token| token == 1)
+            .unwrap();
+        let expected = 1.0 / (1.0 + (-0.5f64).exp());
+        assert!((probabilities[token] - expected).abs() < 1e-12);
+
+        let config = SamplingConfig::new().repetition_penalty(2.0);
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            assert_eq!(config.sample(&[-1.0, -1000.0], &[0], &mut rng), 0);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let config = SamplingConfig::new();
+        let mut rng = rand::thread_rng();
+        assert_eq!(
+            config.try_sample(&[], &[], &mut rng),
+            Err(RouletteError::Empty)
+        );
+        assert_eq!(
+            config.try_sampler(&[0.0, f64::NAN], &[]).err(),
+            Some(RouletteError::NaN { index: 1 })
+        );
+        assert_eq!(
+            config.temperature(0.0).try_sampler(&[0.0], &[]).err(),
+            Some(RouletteError::InvalidTemperature)
+        );
+    }
+}
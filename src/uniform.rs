@@ -0,0 +1,141 @@
+use index::Probability;
+use {Roulette, RouletteIndex};
+
+impl RouletteIndex {
+    /// Returns the index that the alias table gives for two uniforms in
+    /// `0.0..1.0` generated elsewhere, such as by a quasi-Monte Carlo
+    /// sequence: `u_column` chooses the column, and `u_coin` is compared to
+    /// its probability. If both are uniformly distributed, each index is
+    /// returned with its effective probability.
+    ///
+    /// This ignores the `SamplingMethod`. For tables built from integer
+    /// weights, the coin is only as precise as an `f64`.
+    ///
+    /// Panics if the `RouletteIndex` is empty or either uniform isn't in
+    /// `0.0..1.0`.
+    pub fn sample_with_uniforms(&self, u_column: f64, u_coin: f64) -> usize {
+        assert!(!self.is_empty(), "Can't sample from an empty Roulette");
+        assert!(
+            (0.0..1.0).contains(&u_column) && (0.0..1.0).contains(&u_coin),
+            "Uniforms must be in 0.0..1.0"
+        );
+        // The product can round up to the length.
+        let column = ((u_column * self.len() as f64) as usize).min(self.len() - 1);
+        self.flip(column, u_coin)
+    }
+
+    /// Returns the index that the alias table gives for a single uniform in
+    /// `0.0..1.0`: its integer part after scaling by the number of columns
+    /// chooses the column, and the fractional part is used as the coin.
+    ///
+    /// The coin loses about log2(n) bits of precision, so prefer
+    /// `RouletteIndex::sample_with_uniforms` for large tables.
+    ///
+    /// Panics if the `RouletteIndex` is empty or `u` isn't in `0.0..1.0`.
+    pub fn sample_with_uniform(&self, u: f64) -> usize {
+        assert!(!self.is_empty(), "Can't sample from an empty Roulette");
+        assert!((0.0..1.0).contains(&u), "Uniforms must be in 0.0..1.0");
+        let scaled = u * self.len() as f64;
+        let column = scaled as usize;
+        if column >= self.len() {
+            // The product rounded up to the length.
+            return self.flip(self.len() - 1, 0.0);
+        }
+        self.flip(column, scaled - column as f64)
+    }
+
+    /// Returns `column` or its alias, depending on whether `u_coin` is below
+    /// the column's probability.
+    fn flip(&self, column: usize, u_coin: f64) -> usize {
+        let heads = match self.probability {
+            Probability::Float(ref probability) => u_coin < probability[column],
+            Probability::Exact {
+                ref numerators,
+                denominator,
+                ..
+            } => ((u_coin * denominator as f64) as u64) < numerators[column],
+        };
+        if heads {
+            column
+        } else {
+            self.alias[column]
+        }
+    }
+}
+
+impl<T> Roulette<T> {
+    /// Returns the element chosen by two uniforms in `0.0..1.0` generated
+    /// elsewhere; see `RouletteIndex::sample_with_uniforms`.
+    ///
+    /// Panics if the `Roulette` is empty or either uniform isn't in
+    /// `0.0..1.0`.
+    pub fn sample_with_uniforms(&self, u_column: f64, u_coin: f64) -> &T {
+        &self.items[self.index.sample_with_uniforms(u_column, u_coin)]
+    }
+
+    /// Returns the element chosen by a single uniform in `0.0..1.0`; see
+    /// `RouletteIndex::sample_with_uniform`.
+    ///
+    /// Panics if the `Roulette` is empty or `u` isn't in `0.0..1.0`.
+    pub fn sample_with_uniform(&self, u: f64) -> &T {
+        &self.items[self.index.sample_with_uniform(u)]
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use stats::tests::WEIGHTS;
+
+    /// Counts the indices chosen over an evenly spaced grid of `n` points in
+    /// each dimension, and over `n * n` points for the single uniform.
+    fn grid_counts(index: &RouletteIndex, n: usize) -> (Vec<usize>, Vec<usize>) {
+        let point = |k: usize| (k as f64 + 0.5) / n as f64;
+        let mut two = vec![0; index.len()];
+        let mut one = vec![0; index.len()];
+        for i in 0..n {
+            for j in 0..n {
+                two[index.sample_with_uniforms(point(i), point(j))] += 1;
+            }
+        }
+        for k in 0..n * n {
+            one[index.sample_with_uniform((k as f64 + 0.5) / (n * n) as f64)] += 1;
+        }
+        (two, one)
+    }
+
+    #[test]
+    fn grid() {
+        let n = 400;
+        for index in &[
+            RouletteIndex::new(&WEIGHTS),
+            RouletteIndex::from_integer_weights(&[1, 0, 3, 7]),
+        ] {
+            let (two, one) = grid_counts(index, n);
+            let total = (n * n) as f64;
+            for (i, &probability) in index.effective_probabilities().iter().enumerate() {
+                assert!((two[i] as f64 / total - probability).abs() < 1e-2);
+                assert!((one[i] as f64 / total - probability).abs() < 1e-2);
+                if probability == 0.0 {
+                    assert_eq!((two[i], one[i]), (0, 0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let roulette = Roulette::new(vec![('a', 1.0), ('b', 0.0), ('c', 1.0)]);
+        assert_eq!(
+            roulette.sample_with_uniforms(0.3, 0.7),
+            roulette.sample_with_uniforms(0.3, 0.7)
+        );
+        assert_eq!(roulette.sample_with_uniforms(0.0, 0.0), &'a');
+        for &u in &[0.0, 0.5, 0.999_999_999_999] {
+            assert_ne!(roulette.sample_with_uniform(u), &'b');
+            assert_ne!(roulette.sample_with_uniforms(u, u), &'b');
+        }
+    }
+}
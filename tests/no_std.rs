@@ -0,0 +1,46 @@
+//! Checks that the crate still builds without the `std` feature, along with
+//! its tests and examples, and that its doctests pass. The crate is always
+//! `#![no_std]`, so any use of `std` outside of `cfg(feature = "std")` fails
+//! this check; examples and unit tests that need `std` must say so. `cargo
+//! check` doesn't compile doctests, so they're run separately.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn check(features: &str) {
+    cargo(&["check", "--all-targets"], features);
+    cargo(&["test", "--doc"], features);
+}
+
+fn cargo(args: &[&str], features: &str) {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    // A separate target directory avoids waiting on the lock held by the
+    // `cargo test` that's running this.
+    let target_dir = Path::new(manifest_dir).join("target").join("no_std");
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(args)
+        .args(["--no-default-features", "--features", features])
+        .arg("--manifest-path")
+        .arg(Path::new(manifest_dir).join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .expect("Failed to run cargo");
+    assert!(
+        status.success(),
+        "`cargo {}` failed with features {:?}",
+        args.join(" "),
+        features
+    );
+}
+
+#[test]
+fn builds_without_std() {
+    check("");
+}
+
+#[test]
+fn builds_without_std_with_optional_features() {
+    check("serde rand_0_8 rand_0_9 rand_0_10");
+}